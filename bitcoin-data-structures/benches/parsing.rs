@@ -0,0 +1,33 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use bitcoin_data_structures::*;
+
+const BLOCK: &str = include_str!("../block.hex");
+
+fn hex(c: &mut Criterion) {
+    let block_hex = BLOCK.trim();
+    let block_bytes = from_hex(block_hex).unwrap();
+
+    let mut group = c.benchmark_group("hex");
+    group.throughput(Throughput::Bytes(block_hex.len() as u64));
+    group.bench_function("from_hex", |b| b.iter(|| from_hex(black_box(block_hex)).unwrap()));
+    group.bench_function("to_hex", |b| b.iter(|| to_hex(black_box(&block_bytes))));
+    group.bench_function("roundtrip", |b| b.iter(|| from_hex(&to_hex(black_box(&block_bytes))).unwrap()));
+    group.finish();
+}
+
+fn block(c: &mut Criterion) {
+    let block_bytes = from_hex(BLOCK.trim()).unwrap();
+    let block = Block::parse_exact(&block_bytes).unwrap();
+
+    let mut group = c.benchmark_group("block");
+    group.throughput(Throughput::Bytes(block_bytes.len() as u64));
+    group.bench_function("parse", |b| b.iter(|| Block::parse_exact(black_box(&block_bytes)).unwrap()));
+    group.bench_function("header_only", |b| b.iter(|| BlockHeader::parse_from_block_bytes(black_box(&block_bytes)).unwrap()));
+    group.bench_function("decode", |b| b.iter(|| Block::decode(&mut black_box(&block_bytes[..])).unwrap()));
+    group.bench_function("compute_merkle_root", |b| b.iter(|| black_box(&block).compute_merkle_root()));
+    group.finish();
+}
+
+criterion_group!(benches, hex, block);
+criterion_main!(benches);
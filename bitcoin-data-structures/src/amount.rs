@@ -0,0 +1,65 @@
+use core::fmt;
+
+use crate::utils::*;
+
+/// An amount of bitcoin, stored in satoshis
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Amount(pub u64);
+
+impl Amount {
+    pub const SAT_PER_BTC: u64 = 100_000_000;
+    /// The 21 million BTC supply cap
+    pub const MAX_MONEY: Amount = Amount(21_000_000 * Amount::SAT_PER_BTC);
+
+    pub fn from_sat(sat: u64) -> Self {
+        Amount(sat)
+    }
+
+    pub fn to_sat(self) -> u64 {
+        self.0
+    }
+
+    /// Rounds to the nearest satoshi, rejects negative values and anything above `MAX_MONEY`
+    pub fn from_btc(btc: f64) -> Result<Self, Error> {
+        // `f64::round` needs `std`: for the values accepted here, adding half a satoshi and
+        // truncating rounds the same way
+        let sat = btc * Amount::SAT_PER_BTC as f64 + 0.5;
+        if !(sat > 0.0 && sat < (Amount::MAX_MONEY.0 + 1) as f64) {
+            return Err(Error::AmountOutOfRange);
+        }
+
+        Ok(Amount(sat as u64))
+    }
+
+    pub fn to_btc(self) -> f64 {
+        self.0 as f64 / Amount::SAT_PER_BTC as f64
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{:08} BTC", self.0 / Amount::SAT_PER_BTC, self.0 % Amount::SAT_PER_BTC)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_btc_conversion() {
+        assert_eq!(Amount::from_btc(0.00012345), Ok(Amount(12345)));
+        assert_eq!(Amount::from_btc(21_000_000.0), Ok(Amount::MAX_MONEY));
+        assert_eq!(Amount::from_btc(21_000_000.00000001), Err(Error::AmountOutOfRange));
+        assert_eq!(Amount::from_btc(-0.1), Err(Error::AmountOutOfRange));
+        assert_eq!(Amount::from_btc(f64::NAN), Err(Error::AmountOutOfRange));
+        assert_eq!(Amount::from_sat(250_000_000).to_btc(), 2.5);
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Amount(12345).to_string(), "0.00012345 BTC");
+        assert_eq!(Amount(50 * Amount::SAT_PER_BTC).to_string(), "50.00000000 BTC");
+    }
+}
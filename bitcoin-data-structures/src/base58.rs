@@ -0,0 +1,115 @@
+use crate::hashes::sha256d;
+use crate::prelude::*;
+use crate::utils::Error;
+
+const ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+pub fn encode(bytes: &[u8]) -> String {
+    // Every leading zero byte is encoded as a leading '1'
+    let zeros = bytes.iter().take_while(|b| **b == 0).count();
+
+    // Base conversion, digits are stored little-endian
+    let mut digits: Vec<u8> = Vec::new();
+    for byte in &bytes[zeros..] {
+        let mut carry = *byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+
+    core::iter::repeat_n('1', zeros)
+        .chain(digits.iter().rev().map(|d| ALPHABET[*d as usize] as char))
+        .collect()
+}
+
+/// Appends the first four bytes of the double-SHA256 of `payload` before encoding
+pub fn encode_check(payload: &[u8]) -> String {
+    let mut data = payload.to_vec();
+    data.extend_from_slice(&sha256d(payload)[..4]);
+    encode(&data)
+}
+
+pub fn decode(s: &str) -> Result<Vec<u8>, Error> {
+    let zeros = s.chars().take_while(|c| *c == '1').count();
+
+    // Base conversion, bytes are stored little-endian
+    let mut bytes: Vec<u8> = Vec::new();
+    for c in s.chars().skip(zeros) {
+        let mut carry = ALPHABET
+            .iter()
+            .position(|a| *a as char == c)
+            .ok_or(Error::InvalidBase58Char(c))? as u32;
+        for byte in bytes.iter_mut() {
+            carry += *byte as u32 * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+
+    Ok(core::iter::repeat_n(0, zeros).chain(bytes.into_iter().rev()).collect())
+}
+
+/// Decodes and strips the four bytes checksum, erroring if it doesn't match the payload
+pub fn decode_check(s: &str) -> Result<Vec<u8>, Error> {
+    let mut data = decode(s)?;
+    if data.len() < 4 {
+        return Err(Error::InsufficientBytes { needed: 4, got: data.len() });
+    }
+
+    let checksum = data.split_off(data.len() - 4);
+    if checksum != sha256d(&data)[..4] {
+        return Err(Error::InvalidChecksum);
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::from_hex;
+
+    #[test]
+    fn test_encode_decode() {
+        let vectors = [
+            ("", ""),
+            ("61", "2g"),
+            ("626262", "a3gV"),
+            ("73696d706c792061206c6f6e6720737472696e67", "2cFupjhnEsSn59qHXstmK2ffpLv2"),
+            ("00000001", "1112"),
+            ("00eb15231dfceb60925886b67d065299925915aeb172c06647", "1NS17iag9jJgTHD1VXjvLCEnZuQ3rJDE9L"),
+        ];
+        for (hex, base58) in vectors {
+            assert_eq!(encode(&from_hex(hex).unwrap()), base58);
+            assert_eq!(decode(base58), Ok(from_hex(hex).unwrap()));
+        }
+
+        assert_eq!(decode("0OIl"), Err(Error::InvalidBase58Char('0')));
+    }
+
+    #[test]
+    fn test_encode_decode_check() {
+        let vectors = [
+            ("0062e907b15cbf27d5425399ebf6f0fb50ebb88f18", "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa"),
+            ("0011b366edfc0a8b66feebae5c2e25a7b6a5d1cf31", "12cbQLTFMXRnSzktFkuoG3eHoMeFtpTu3S"),
+            ("05b472a266d0bd89c13706a4132ccfb16f7c3b9fcb", "3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy"),
+        ];
+        for (hex, address) in vectors {
+            assert_eq!(encode_check(&from_hex(hex).unwrap()), address);
+            assert_eq!(decode_check(address), Ok(from_hex(hex).unwrap()));
+        }
+
+        assert_eq!(decode_check("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNb"), Err(Error::InvalidChecksum));
+        assert_eq!(decode_check("1A1"), Err(Error::InsufficientBytes { needed: 4, got: 3 }));
+    }
+}
@@ -0,0 +1,236 @@
+use crate::prelude::*;
+use crate::utils::Error;
+
+const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+const BECH32_CONST: u32 = 1;
+const BECH32M_CONST: u32 = 0x2bc830a3;
+
+fn polymod(values: &[u8]) -> u32 {
+    const GEN: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+
+    let mut chk = 1u32;
+    for v in values {
+        let top = chk >> 25;
+        chk = (chk & 0x1ffffff) << 5 ^ *v as u32;
+        for (i, g) in GEN.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                chk ^= g;
+            }
+        }
+    }
+    chk
+}
+
+fn hrp_expand(hrp: &str) -> Vec<u8> {
+    let mut expanded = hrp.bytes().map(|b| b >> 5).collect::<Vec<_>>();
+    expanded.push(0);
+    expanded.extend(hrp.bytes().map(|b| b & 0x1f));
+    expanded
+}
+
+fn create_checksum(hrp: &str, data: &[u8], constant: u32) -> [u8; 6] {
+    let mut values = hrp_expand(hrp);
+    values.extend_from_slice(data);
+    values.extend_from_slice(&[0; 6]);
+
+    let polymod = polymod(&values) ^ constant;
+    let mut checksum = [0u8; 6];
+    for (i, c) in checksum.iter_mut().enumerate() {
+        *c = ((polymod >> (5 * (5 - i))) & 0x1f) as u8;
+    }
+    checksum
+}
+
+/// Regroups 8-bit bytes into 5-bit groups, padding the last one with zeros
+fn to_5bit(bytes: &[u8]) -> Vec<u8> {
+    let mut acc = 0u32;
+    let mut bits = 0;
+    let mut out = Vec::new();
+    for b in bytes {
+        acc = acc << 8 | *b as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(((acc >> bits) & 0x1f) as u8);
+        }
+    }
+    if bits > 0 {
+        out.push(((acc << (5 - bits)) & 0x1f) as u8);
+    }
+    out
+}
+
+/// Inverse of `to_5bit`, rejecting incomplete groups and non-zero padding
+fn from_5bit(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut acc = 0u32;
+    let mut bits = 0;
+    let mut out = Vec::new();
+    for d in data {
+        acc = acc << 5 | *d as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push(((acc >> bits) & 0xff) as u8);
+        }
+    }
+    if bits >= 5 || (acc & ((1 << bits) - 1)) != 0 {
+        return Err(Error::InvalidBech32Padding);
+    }
+    Ok(out)
+}
+
+/// Encodes a segwit address: Bech32 for witness version 0, Bech32m (BIP350) for versions 1 to 16
+pub fn encode(hrp: &str, witver: u8, program: &[u8]) -> String {
+    let constant = if witver == 0 { BECH32_CONST } else { BECH32M_CONST };
+
+    let mut data = vec![witver];
+    data.extend(to_5bit(program));
+    let checksum = create_checksum(hrp, &data, constant);
+
+    let mut address = String::from(hrp);
+    address.push('1');
+    address.extend(data.iter().chain(checksum.iter()).map(|d| CHARSET[*d as usize] as char));
+    address
+}
+
+/// Decodes a segwit address into its human readable part, witness version and program
+pub fn decode(address: &str) -> Result<(String, u8, Vec<u8>), Error> {
+    if address.len() > 90 {
+        return Err(Error::InvalidBech32Length(address.len()));
+    }
+    if address.chars().any(|c| c.is_ascii_lowercase()) && address.chars().any(|c| c.is_ascii_uppercase()) {
+        return Err(Error::MixedCaseBech32);
+    }
+    let address = address.to_ascii_lowercase();
+
+    let (hrp, data) = address.rsplit_once('1').ok_or(Error::MissingBech32Separator)?;
+    if let Some(c) = hrp.chars().find(|c| !(33..=126).contains(&(*c as u32))) {
+        return Err(Error::InvalidBech32Char(c));
+    }
+    if hrp.is_empty() || data.len() < 7 {
+        return Err(Error::InvalidBech32Length(address.len()));
+    }
+
+    let data = data
+        .chars()
+        .map(|c| CHARSET.iter().position(|x| *x as char == c).map(|d| d as u8).ok_or(Error::InvalidBech32Char(c)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // BIP350: witness version 0 uses Bech32, any later version uses Bech32m
+    let witver = data[0];
+    if witver > 16 {
+        return Err(Error::InvalidWitnessVersion(witver));
+    }
+    let constant = if witver == 0 { BECH32_CONST } else { BECH32M_CONST };
+
+    let mut values = hrp_expand(hrp);
+    values.extend_from_slice(&data);
+    if polymod(&values) != constant {
+        return Err(Error::InvalidChecksum);
+    }
+
+    let program = from_5bit(&data[1..data.len() - 6])?;
+    if !(2..=40).contains(&program.len()) || (witver == 0 && program.len() != 20 && program.len() != 32) {
+        return Err(Error::InvalidWitnessProgramLength(program.len()));
+    }
+
+    Ok((hrp.to_string(), witver, program))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::from_hex;
+
+    #[test]
+    fn test_valid_addresses() {
+        // Witness v0 vectors from BIP173
+        let vectors = [
+            ("BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4", "bc", "751e76e8199196d454941c45d1b3a323f1433bd6"),
+            (
+                "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7",
+                "tb",
+                "1863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262",
+            ),
+            (
+                "tb1qqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesrxh6hy",
+                "tb",
+                "000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433",
+            ),
+        ];
+        for (address, hrp, program) in vectors {
+            let program = from_hex(program).unwrap();
+            assert_eq!(decode(address), Ok((hrp.to_string(), 0, program.clone())));
+            assert_eq!(encode(hrp, 0, &program), address.to_lowercase());
+        }
+    }
+
+    #[test]
+    fn test_valid_bech32m_addresses() {
+        // Witness v1+ vectors from BIP350
+        let vectors = [
+            (
+                "bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7kt5nd6y",
+                "bc",
+                1,
+                "751e76e8199196d454941c45d1b3a323f1433bd6751e76e8199196d454941c45d1b3a323f1433bd6",
+            ),
+            ("BC1SW50QGDZ25J", "bc", 16, "751e"),
+            (
+                "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+                "bc",
+                1,
+                "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            ),
+            (
+                "tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c",
+                "tb",
+                1,
+                "000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433",
+            ),
+        ];
+        for (address, hrp, witver, program) in vectors {
+            let program = from_hex(program).unwrap();
+            assert_eq!(decode(address), Ok((hrp.to_string(), witver, program.clone())));
+            assert_eq!(encode(hrp, witver, &program), address.to_lowercase());
+        }
+    }
+
+    #[test]
+    fn test_wrong_checksum_variant() {
+        // Invalid vectors from BIP350: v1+ with a Bech32 checksum and v0 with a Bech32m one
+        for address in [
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqh2y7hd",
+            "BC1S0XLXVLHEMJA6C4DQV22UAPCTQUPFHLXM9H8Z3K2E72Q4K9HCZ7VQ54WELL",
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kemeawh",
+            "tb1q0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq24jc47",
+        ] {
+            assert_eq!(decode(address), Err(Error::InvalidChecksum));
+        }
+    }
+
+    #[test]
+    fn test_invalid_addresses() {
+        // Invalid vectors from BIP173, and from BIP350 where the v1+ checksum is now Bech32m
+        assert_eq!(decode("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5"), Err(Error::InvalidChecksum));
+        assert_eq!(decode("BC130XLXVLHEMJA6C4DQV22UAPCTQUPFHLXM9H8Z3K2E72Q4K9HCZ7VQ7ZWS8R"), Err(Error::InvalidWitnessVersion(17)));
+        assert_eq!(decode("bc1pw5dgrnzv"), Err(Error::InvalidWitnessProgramLength(1)));
+        assert_eq!(
+            decode("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7v8n0nx0muaewav253zgeav"),
+            Err(Error::InvalidWitnessProgramLength(41))
+        );
+        assert_eq!(decode("BC1QR508D6QEJXTDG4Y5R3ZARVARYV98GJ9P"), Err(Error::InvalidWitnessProgramLength(16)));
+        assert_eq!(
+            decode("tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sL5k7"),
+            Err(Error::MixedCaseBech32)
+        );
+        assert_eq!(
+            decode("tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3pjxtptv"),
+            Err(Error::InvalidBech32Padding)
+        );
+        assert_eq!(decode("bc1gmk9yu"), Err(Error::InvalidBech32Length(9)));
+        assert_eq!(decode("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3tb"), Err(Error::InvalidBech32Char('b')));
+        assert_eq!(decode("qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"), Err(Error::MissingBech32Separator));
+    }
+}
@@ -0,0 +1,1349 @@
+use core::fmt;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::io::Read;
+#[cfg(feature = "std")]
+use std::path::Path;
+
+use crate::amount::Amount;
+use crate::encoding::*;
+use crate::hashes::{sha256, sha256d, siphash24, BlockHash, Txid};
+use crate::network::Network;
+use crate::prelude::*;
+use crate::script::OpCode;
+use crate::script_num;
+use crate::transaction::{OutPoint, Transaction, TxOut};
+use crate::utils::*;
+
+/// Serialized size of a `BlockHeader`
+const HEADER_SIZE: usize = 80;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BlockHeader {
+    pub version: i32,
+    #[cfg_attr(feature = "serde", serde(rename = "previousblockhash", with = "crate::serde_utils::reversed_hex"))]
+    pub prev_block: [u8; 32],
+    #[cfg_attr(feature = "serde", serde(rename = "merkleroot", with = "crate::serde_utils::reversed_hex"))]
+    pub merkle_root: [u8; 32],
+    #[cfg_attr(feature = "serde", serde(rename = "time"))]
+    pub timestamp: u32,
+    pub bits: u32,
+    pub nonce: u32,
+}
+
+impl BlockHeader {
+    /// Double-SHA256 of the 80 bytes header
+    pub fn block_hash(&self) -> BlockHash {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE);
+        self.encode(&mut bytes);
+        BlockHash(sha256d(&bytes))
+    }
+
+    /// Parses the header of a serialized block, the transactions that follow aren't looked at
+    pub fn parse_from_block_bytes(bytes: &[u8]) -> Result<BlockHeader, Error> {
+        BlockHeader::parse(bytes).map(|(header, _)| header)
+    }
+
+    /// Like `parse_from_block_bytes` for a hex-encoded block, only the header is decoded
+    pub fn from_block_hex(s: &str) -> Result<BlockHeader, Error> {
+        let s = s.trim_start();
+        BlockHeader::parse_from_block_bytes(&from_hex(s.get(..HEADER_SIZE * 2).unwrap_or(s))?)
+    }
+
+    /// Hash of the parent block, `prev_block` as a typed hash
+    pub fn prev_blockhash(&self) -> BlockHash {
+        BlockHash(self.prev_block)
+    }
+
+    /// The header timestamp as `YYYY-MM-DD HH:MM:SS UTC`.
+    ///
+    /// The field is an unsigned 32-bit number of seconds, so the latest representable time is
+    /// 2106-02-07 06:28:15 UTC; the format itself has no way to go past it
+    pub fn time(&self) -> String {
+        format_utc(self.timestamp)
+    }
+
+    /// Expands the compact `bits` into the 256-bit target, as a big-endian number.
+    /// Negative or overflowing encodings yield a zero target that no hash can meet
+    pub fn target(&self) -> [u8; 32] {
+        let exponent = (self.bits >> 24) as usize;
+        let mantissa = self.bits & 0x007fffff;
+        let negative = self.bits & 0x00800000 != 0;
+        let overflow = exponent > 34 || (mantissa > 0xff && exponent > 33) || (mantissa > 0xffff && exponent > 32);
+
+        let mut target = [0; 32];
+        if mantissa == 0 || negative || overflow {
+            return target;
+        }
+
+        // The mantissa is the most significant 3 bytes of a number `exponent` bytes long
+        for (i, byte) in mantissa.to_be_bytes()[1..].iter().enumerate() {
+            if let Some(pos) = (32 + i).checked_sub(exponent) {
+                if pos < 32 {
+                    target[pos] = *byte;
+                }
+            }
+        }
+
+        target
+    }
+
+    /// Ratio between the difficulty-1 target (`0x1d00ffff`) and this block's target,
+    /// computed the same way as Bitcoin Core's `GetDifficulty`
+    pub fn difficulty(&self) -> f64 {
+        let mut shift = (self.bits >> 24) & 0xff;
+        let mut difficulty = 0x0000ffff as f64 / (self.bits & 0x00ffffff) as f64;
+        while shift < 29 {
+            difficulty *= 256.0;
+            shift += 1;
+        }
+        while shift > 29 {
+            difficulty /= 256.0;
+            shift -= 1;
+        }
+
+        difficulty
+    }
+
+    /// Whether the block hash, read as a big-endian number, is not above the target
+    pub fn pow_valid(&self) -> bool {
+        let target = self.target();
+        let mut hash = self.block_hash().0;
+        hash.reverse();
+        target != [0; 32] && hash <= target
+    }
+}
+
+/// Hex-encoded 80 bytes header, surrounding whitespace is ignored
+impl FromStr for BlockHeader {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BlockHeader::parse_exact(&from_hex(s.trim())?)
+    }
+}
+
+/// Exactly 80 bytes
+impl TryFrom<&[u8]> for BlockHeader {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        BlockHeader::parse_exact(bytes)
+    }
+}
+
+impl Parse for BlockHeader {
+    fn parse(bytes: &[u8]) -> Result<(Self, &[u8]), Error> {
+        let (version, bytes) = Parse::parse(bytes)?;
+        let (prev_block, bytes) = Parse::parse(bytes)?;
+        let (merkle_root, bytes) = Parse::parse(bytes)?;
+        let (timestamp, bytes) = Parse::parse(bytes)?;
+        let (bits, bytes) = Parse::parse(bytes)?;
+        let (nonce, bytes) = Parse::parse(bytes)?;
+
+        let header = BlockHeader {
+            version, prev_block, merkle_root, timestamp, bits, nonce,
+        };
+
+        Ok((header, bytes))
+    }
+}
+
+#[cfg(feature = "std")]
+impl Decode for BlockHeader {
+    fn decode<R: Read>(r: &mut R) -> Result<Self, Error> {
+        let header = BlockHeader {
+            version: Decode::decode(r)?,
+            prev_block: Decode::decode(r)?,
+            merkle_root: Decode::decode(r)?,
+            timestamp: Decode::decode(r)?,
+            bits: Decode::decode(r)?,
+            nonce: Decode::decode(r)?,
+        };
+
+        Ok(header)
+    }
+}
+
+impl Encode for BlockHeader {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.version.encode(out);
+        self.prev_block.encode(out);
+        self.merkle_root.encode(out);
+        self.timestamp.encode(out);
+        self.bits.encode(out);
+        self.nonce.encode(out);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Block {
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub header: BlockHeader,
+    #[cfg_attr(feature = "serde", serde(rename = "tx"))]
+    pub transactions: Vec<Transaction>,
+}
+
+/// Mainnet genesis block, the other networks share its coinbase and only change the header
+const GENESIS_BLOCK: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c0101000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000";
+
+impl Block {
+    pub fn genesis(network: Network) -> Block {
+        let mut block = Block::from_hex_str(GENESIS_BLOCK).expect("hardcoded genesis block is valid");
+        let (timestamp, bits, nonce) = match network {
+            Network::Mainnet => return block,
+            Network::Testnet => (1296688602, 0x1d00ffff, 414098458),
+            Network::Regtest => (1296688602, 0x207fffff, 2),
+            Network::Signet => (1598918400, 0x1e0377ae, 52613770),
+        };
+        block.header = BlockHeader { timestamp, bits, nonce, ..block.header };
+
+        block
+    }
+
+    /// Parses a hex-encoded block, surrounding whitespace is ignored
+    pub fn from_hex_str(s: &str) -> Result<Block, Error> {
+        Block::parse_exact(&from_hex(s.trim())?)
+    }
+
+    /// Reads a file containing a hex-encoded block, see `from_hex_str`
+    #[cfg(feature = "std")]
+    pub fn from_hex_file<P: AsRef<Path>>(path: P) -> Result<Block, Error> {
+        Block::from_hex_str(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn compute_merkle_root(&self) -> [u8; 32] {
+        #[cfg(feature = "rayon")]
+        let txids = {
+            use rayon::prelude::*;
+            self.transactions.par_iter().map(|tx| tx.txid().0).collect()
+        };
+        #[cfg(not(feature = "rayon"))]
+        let txids = self.transactions.iter().map(|tx| tx.txid().0).collect();
+
+        merkle_root(txids)
+    }
+
+    pub fn txids(&self) -> Vec<Txid> {
+        self.transactions.iter().map(Transaction::txid).collect()
+    }
+
+    /// BIP141 wtxids, the coinbase one being all zeros as in the witness merkle tree
+    pub fn wtxids(&self) -> Vec<Txid> {
+        self.transactions.iter().enumerate().map(|(i, tx)| if i == 0 { Txid([0; 32]) } else { tx.wtxid() }).collect()
+    }
+
+    /// BIP152 short ids of the transactions: SipHash-2-4 of the wtxids, the real one for the
+    /// coinbase too, keyed by the SHA256 of the header followed by `nonce`, truncated to 6 bytes
+    pub fn short_ids(&self, nonce: u64) -> Vec<u64> {
+        let mut data = Vec::new();
+        self.header.encode(&mut data);
+        nonce.encode(&mut data);
+        let key = sha256(&data);
+        let k0 = u64::from_le_bytes(key[..8].try_into().expect("8 bytes"));
+        let k1 = u64::from_le_bytes(key[8..16].try_into().expect("8 bytes"));
+
+        self.transactions.iter().map(|tx| siphash24(k0, k1, &tx.wtxid().0) & 0xffff_ffff_ffff).collect()
+    }
+
+    /// Serialized size in bytes, witness data included
+    pub fn size(&self) -> usize {
+        let mut bytes = Vec::new();
+        self.encode(&mut bytes);
+        bytes.len()
+    }
+
+    /// Serialized size in bytes with every transaction in its legacy form
+    pub fn stripped_size(&self) -> usize {
+        HEADER_SIZE + VarInt::from(self.transactions.len() as u64).encoded_len() + self.transactions.iter().map(Transaction::base_size).sum::<usize>()
+    }
+
+    /// BIP141 weight, the consensus limit is 4,000,000
+    pub fn weight(&self) -> usize {
+        let header_size = HEADER_SIZE + VarInt::from(self.transactions.len() as u64).encoded_len();
+        header_size * 4 + self.transactions.iter().map(Transaction::weight).sum::<usize>()
+    }
+
+    /// Every output created by the block, with the outpoint that would spend it
+    pub fn outputs(&self) -> impl Iterator<Item = (OutPoint, &TxOut)> {
+        self.transactions.iter().flat_map(|tx| {
+            let txid = tx.txid().0;
+            tx.outputs.iter().enumerate().map(move |(vout, txout)| (OutPoint { txid, vout: vout as u32 }, txout))
+        })
+    }
+
+    /// Every outpoint spent by the block, the coinbase input excluded
+    pub fn spent_outpoints(&self) -> impl Iterator<Item = &OutPoint> {
+        self.transactions.iter().filter(|tx| !tx.is_coinbase()).flat_map(|tx| tx.inputs.iter().map(|txin| &txin.previous_output))
+    }
+
+    /// Connects the block to a UTXO set: removes the outpoints spent by each transaction and
+    /// inserts its spendable outputs, in block order so outputs spent in the same block are
+    /// never left behind
+    #[cfg(feature = "std")]
+    pub fn apply_to_utxos(&self, utxos: &mut HashMap<OutPoint, TxOut>) {
+        for tx in &self.transactions {
+            if !tx.is_coinbase() {
+                for txin in &tx.inputs {
+                    utxos.remove(&txin.previous_output);
+                }
+            }
+
+            let txid = tx.txid().0;
+            for (vout, txout) in tx.outputs.iter().enumerate() {
+                if !txout.script_pubkey.is_unspendable() {
+                    utxos.insert(OutPoint { txid, vout: vout as u32 }, txout.clone());
+                }
+            }
+        }
+    }
+
+    /// BIP34 height, the first push of the coinbase scriptSig
+    pub fn coinbase_height(&self) -> Option<i64> {
+        let coinbase = self.transactions.first().filter(|tx| tx.is_coinbase())?;
+        let (first, _) = OpCode::parse(coinbase.inputs[0].script_sig.as_bytes()).ok()?;
+        match first {
+            OpCode::False => Some(0),
+            OpCode::Num(n) => Some(n.get() as i64),
+            OpCode::Push(data) => script_num::decode_with_max_size(&data, 8).ok(),
+            _ => None,
+        }
+    }
+
+    /// Sum of the output values of every transaction, coinbase included
+    pub fn total_output_value(&self) -> Result<u64, Error> {
+        self.transactions.iter().try_fold(0u64, |sum, tx| sum.checked_add(tx.total_output_value()?).ok_or(Error::ValueOverflow))
+    }
+
+    /// Sum of the fees paid by every non-coinbase transaction, see `Transaction::fee`. For a valid
+    /// block this is at least the coinbase output value minus `block_subsidy`
+    pub fn total_fees<F: Fn(&OutPoint) -> Option<u64>>(&self, get_value: F) -> Result<u64, Error> {
+        self.transactions
+            .iter()
+            .filter(|tx| !tx.is_coinbase())
+            .try_fold(0u64, |sum, tx| sum.checked_add(tx.fee(&get_value)?).ok_or(Error::ValueOverflow))
+    }
+
+    /// Structural coinbase rules: the first transaction, and no other, is a coinbase with a
+    /// single input and a 2 to 100 bytes scriptSig
+    pub fn validate_coinbase(&self) -> Result<(), Error> {
+        let coinbase = self.transactions.first().ok_or(Error::EmptyBlock)?;
+        if !coinbase.inputs.iter().any(|txin| txin.previous_output.is_coinbase()) {
+            return Err(Error::MissingCoinbase);
+        }
+        if coinbase.inputs.len() != 1 {
+            return Err(Error::CoinbaseInputCount(coinbase.inputs.len()));
+        }
+        let txin = &coinbase.inputs[0];
+        let script_len = txin.script_sig.size();
+        if !(2..=100).contains(&script_len) {
+            return Err(Error::CoinbaseScriptSize(script_len));
+        }
+
+        match self.transactions.iter().skip(1).position(|tx| tx.is_coinbase()) {
+            Some(index) => Err(Error::ExtraCoinbase(index + 1)),
+            None => Ok(()),
+        }
+    }
+
+    /// Fails on the first transaction spending the same outpoint twice, see
+    /// `Transaction::has_duplicate_inputs`
+    pub fn check_no_duplicate_inputs(&self) -> Result<(), Error> {
+        match self.transactions.iter().find(|tx| tx.has_duplicate_inputs()) {
+            Some(tx) => Err(Error::DuplicateInputs(tx.txid())),
+            None => Ok(()),
+        }
+    }
+
+    pub fn verify_merkle_root(&self) -> bool {
+        self.compute_merkle_root() == self.header.merkle_root
+    }
+
+    pub fn op_return_payloads(&self) -> Vec<Vec<u8>> {
+        self.transactions
+            .iter()
+            .flat_map(|tx| &tx.outputs)
+            .filter_map(|txout| txout.script_pubkey.op_return_data())
+            .collect()
+    }
+
+    /// BIP141 commitment to the witness root, in the highest-index coinbase output that
+    /// starts with `OP_RETURN OP_PUSHBYTES_36 aa21a9ed`
+    pub fn witness_commitment(&self) -> Option<[u8; 32]> {
+        let coinbase = self.transactions.first().filter(|tx| tx.is_coinbase())?;
+        coinbase.outputs.iter().rev().find_map(|txout| {
+            match txout.script_pubkey.as_bytes() {
+                [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed, commitment @ ..] if commitment.len() >= 32 => commitment[..32].try_into().ok(),
+                _ => None,
+            }
+        })
+    }
+
+    /// Recomputes the witness merkle root (with an all-zero coinbase wtxid), hashes it with the
+    /// coinbase witness reserved value and compares it to `witness_commitment`. A block without
+    /// commitment is only valid if none of its transactions have witness data
+    pub fn verify_witness_commitment(&self) -> bool {
+        let Some(commitment) = self.witness_commitment() else {
+            return !self.transactions.iter().any(Transaction::has_witness);
+        };
+        let reserved = match self.transactions[0].inputs[0].witness.as_slice() {
+            [reserved] if reserved.len() == 32 => reserved,
+            _ => return false,
+        };
+
+        let mut data = merkle_root(self.wtxids().into_iter().map(|wtxid| wtxid.0).collect()).to_vec();
+        data.extend_from_slice(reserved);
+        sha256d(&data) == commitment
+    }
+
+    /// Proof that the transaction is included under the merkle root, `None` if it isn't in the block
+    pub fn merkle_proof(&self, txid: &Txid) -> Option<MerkleProof> {
+        let mut level = self.transactions.iter().map(|tx| tx.txid().0).collect::<Vec<_>>();
+        let index = level.iter().position(|h| h == &txid.0)?;
+
+        let mut path = Vec::new();
+        let mut position = index;
+        while level.len() > 1 {
+            let sibling = level.get(position ^ 1).unwrap_or(&level[position]);
+            path.push(*sibling);
+
+            level = merkle_level(&level);
+            position /= 2;
+        }
+
+        Some(MerkleProof { index, path })
+    }
+}
+
+impl IntoIterator for Block {
+    type Item = Transaction;
+    type IntoIter = alloc::vec::IntoIter<Transaction>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.transactions.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Block {
+    type Item = &'a Transaction;
+    type IntoIter = core::slice::Iter<'a, Transaction>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.transactions.iter()
+    }
+}
+
+impl fmt::Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let header = &self.header;
+        writeln!(f, "Block {}", header.block_hash())?;
+        writeln!(f, "  version:      {:#010x}", header.version)?;
+        writeln!(f, "  prev block:   {}", BlockHash(header.prev_block))?;
+        writeln!(f, "  merkle root:  {}", Txid(header.merkle_root))?;
+        writeln!(f, "  time:         {}", header.time())?;
+        writeln!(f, "  bits:         {:#010x}", header.bits)?;
+        writeln!(f, "  nonce:        {}", header.nonce)?;
+        write!(f, "  transactions: {}", self.transactions.len())?;
+        for tx in &self.transactions {
+            write!(f, "\n    {} {:>3} in {:>3} out  ", tx.txid(), tx.inputs.len(), tx.outputs.len())?;
+            match tx.total_output_value() {
+                Ok(value) => write!(f, "{}", Amount(value))?,
+                Err(_) => write!(f, "value overflow")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// `YYYY-MM-DD HH:MM:SS UTC` for a Unix timestamp
+fn format_utc(timestamp: u32) -> String {
+    let days = (timestamp / 86400) as i64;
+    let secs = timestamp % 86400;
+
+    // Days to a proleptic Gregorian date, from Howard Hinnant's `civil_from_days`
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC", year, month, day, secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// Iterates over the blocks of a `blkNNNNN.dat` file, each prefixed by the 4 magic bytes (as
+/// they appear on disk) and a 4 bytes length. Core preallocates these files, so a zero magic
+/// is treated as the end of the data. Iteration stops after the first error
+#[cfg(feature = "std")]
+pub fn parse_block_file<R: Read>(r: &mut R, expected_magic: [u8; 4]) -> impl Iterator<Item = Result<Block, Error>> + '_ {
+    let mut done = false;
+    core::iter::from_fn(move || {
+        if done {
+            return None;
+        }
+
+        let result = read_block_file_entry(r, expected_magic).transpose();
+        done = !matches!(result, Some(Ok(_)));
+        result
+    })
+}
+
+#[cfg(feature = "std")]
+fn read_block_file_entry<R: Read>(r: &mut R, expected_magic: [u8; 4]) -> Result<Option<Block>, Error> {
+    let mut magic = [0; 4];
+    let read = r.read(&mut magic)?;
+    if read == 0 {
+        return Ok(None);
+    }
+    r.read_exact(&mut magic[read..])?;
+    if magic == [0; 4] {
+        return Ok(None);
+    }
+    if magic != expected_magic {
+        return Err(Error::InvalidMagic(magic));
+    }
+
+    let len = u32::decode(r)?;
+    Block::parse_exact(&read_vec(r, len as u64)?).map(Some)
+}
+
+/// Whether `child` builds directly on top of `parent`
+pub fn connects_to(child: &BlockHeader, parent: &BlockHeader) -> bool {
+    child.prev_blockhash() == parent.block_hash()
+}
+
+/// Inclusion proof from `Block::merkle_proof`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MerkleProof {
+    /// Position of the transaction in the block, tells on which side each sibling goes
+    pub index: usize,
+    /// Sibling hashes from the transaction up to the merkle root, bottom to top
+    pub path: Vec<[u8; 32]>,
+}
+
+/// Number of previous blocks whose median timestamp a new block must exceed
+pub const MEDIAN_TIME_SPAN: usize = 11;
+
+/// Why `validate_header_chain` rejected a chain, with the index of the offending header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChainError {
+    /// `prev_block` isn't the hash of the previous header
+    Disconnected(usize),
+    /// The timestamp isn't above the median of the previous `MEDIAN_TIME_SPAN` headers
+    TimestampTooOld(usize),
+    /// The hash doesn't meet the header's own target
+    InvalidPow(usize),
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChainError::Disconnected(index) => write!(f, "Header {} doesn't connect to the previous one", index),
+            ChainError::TimestampTooOld(index) => write!(f, "Header {} is not after the median time past", index),
+            ChainError::InvalidPow(index) => write!(f, "Header {} has an invalid proof of work", index),
+        }
+    }
+}
+
+/// Checks that consecutive headers connect, that each timestamp is above the median time past
+/// (of the headers available in the slice) and, with `check_pow`, that each hash meets its
+/// target. The `bits` themselves aren't checked against the difficulty adjustment rules
+pub fn validate_header_chain(headers: &[BlockHeader], check_pow: bool) -> Result<(), ChainError> {
+    for (index, header) in headers.iter().enumerate() {
+        if index > 0 {
+            if !connects_to(header, &headers[index - 1]) {
+                return Err(ChainError::Disconnected(index));
+            }
+
+            let mut times = headers[index.saturating_sub(MEDIAN_TIME_SPAN)..index].iter().map(|header| header.timestamp).collect::<Vec<_>>();
+            times.sort_unstable();
+            if header.timestamp <= times[times.len() / 2] {
+                return Err(ChainError::TimestampTooOld(index));
+            }
+        }
+        if check_pow && !header.pow_valid() {
+            return Err(ChainError::InvalidPow(index));
+        }
+    }
+
+    Ok(())
+}
+
+/// Coinbase subsidy in satoshis: 50 BTC halving every 210,000 blocks
+pub fn block_subsidy(height: u64) -> u64 {
+    let halvings = height / 210_000;
+    if halvings >= 64 {
+        return 0;
+    }
+
+    (50 * 100_000_000) >> halvings
+}
+
+/// Replays a proof from `Block::merkle_proof` and checks it ends at `root`
+pub fn verify_merkle_proof(txid: &Txid, proof: &MerkleProof, root: &[u8; 32]) -> bool {
+    let mut index = proof.index;
+    let mut hash = txid.0;
+    for sibling in &proof.path {
+        hash = if index & 1 == 0 {
+            merkle_parent(&hash, sibling)
+        } else {
+            merkle_parent(sibling, &hash)
+        };
+        index /= 2;
+    }
+
+    index == 0 && &hash == root
+}
+
+fn merkle_parent(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut concat = [0u8; 64];
+    concat[..32].copy_from_slice(left);
+    concat[32..].copy_from_slice(right);
+    sha256d(&concat)
+}
+
+/// Levels with an odd number of hashes pair the last one with itself
+fn merkle_level(level: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    level
+        .chunks(2)
+        .map(|pair| merkle_parent(&pair[0], pair.last().expect("chunks are not empty")))
+        .collect()
+}
+
+fn merkle_root(mut level: Vec<[u8; 32]>) -> [u8; 32] {
+    if level.is_empty() {
+        return [0; 32];
+    }
+
+    while level.len() > 1 {
+        level = merkle_level(&level);
+    }
+
+    level[0]
+}
+
+/// Same as `Block::from_hex_str`
+impl FromStr for Block {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Block::from_hex_str(s)
+    }
+}
+
+/// Same as `Block::parse_exact`
+impl TryFrom<&[u8]> for Block {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Block::parse_exact(bytes)
+    }
+}
+
+impl Parse for Block {
+    fn parse(bytes: &[u8]) -> Result<(Self, &[u8]), Error> {
+        Block::parse_with_limits(bytes, &ParseLimits::UNLIMITED)
+    }
+}
+
+impl Block {
+    /// Like `parse`, failing with `Error::LimitExceeded` when the data goes over `limits`. Bytes
+    /// following the block don't count towards `max_total_bytes`
+    pub fn parse_with_limits<'b>(bytes: &'b [u8], limits: &ParseLimits) -> Result<(Self, &'b [u8]), Error> {
+        // Nothing past `max_total_bytes` is read, running out of bytes there means the block is
+        // larger. `got` is then a lower bound of its size
+        let bounded = &bytes[..bytes.len().min(limits.max_total_bytes)];
+        match Block::parse_bounded(bounded, limits) {
+            Ok((block, remainder)) => Ok((block, &bytes[bounded.len() - remainder.len()..])),
+            Err(Error::InsufficientBytes { needed, got }) if bounded.len() < bytes.len() => {
+                Err(Error::LimitExceeded { got: (bounded.len() + needed - got) as u64, max: limits.max_total_bytes })
+            },
+            Err(err) => Err(err),
+        }
+    }
+
+    fn parse_bounded<'b>(bytes: &'b [u8], limits: &ParseLimits) -> Result<(Self, &'b [u8]), Error> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("Block::parse", len = bytes.len()).entered();
+
+        let (header, bytes) = Parse::parse(bytes)?;
+        // Same as `parse_vec_with_limit`, unrolled to know which transaction fails
+        let (count, mut bytes) = VarInt::parse_with_mode(bytes, limits.varint_mode)?;
+        if count.0 > limits.max_transactions as u64 {
+            return Err(Error::LimitExceeded { got: count.0, max: limits.max_transactions });
+        }
+        let mut transactions = Vec::with_capacity(count.0.min(MAX_VEC_PREALLOC) as usize);
+        for _index in 0..count.0 {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::trace_span!("transaction", index = _index, remaining = bytes.len()).entered();
+
+            let result = Transaction::parse_with_limits(bytes, limits);
+            #[cfg(feature = "tracing")]
+            if let Err(err) = &result {
+                tracing::debug!(%err, "failed to parse transaction");
+            }
+            let (tx, remainder) = result?;
+            transactions.push(tx);
+            bytes = remainder;
+        }
+
+        let block = Block {
+            header, transactions
+        };
+
+        Ok((block, bytes))
+    }
+}
+
+#[cfg(feature = "std")]
+impl Decode for Block {
+    fn decode<R: Read>(r: &mut R) -> Result<Self, Error> {
+        Ok(Block { header: Decode::decode(r)?, transactions: Decode::decode(r)? })
+    }
+}
+
+impl Encode for Block {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.header.encode(out);
+        self.transactions.encode(out);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::script::{Script, MAX_SCRIPT_SIZE};
+
+    const BLOCK: &str = include_str!("../block.hex");
+
+    const BLOCK_1_HEADER: &str = "010000006fe28c0ab6f1b372c1a6a246ae63f74f931e8365e15a089c68d6190000000000982051fd1e4ba744bbbe680e1fee14677ba1a3c3540bf7b1cdb606e857233e0e61bc6649ffff001d01e36299";
+
+    fn genesis_header() -> BlockHeader {
+        Block::genesis(Network::Mainnet).header
+    }
+
+    #[test]
+    fn test_block_hash() {
+        let genesis = genesis_header();
+        assert_eq!(genesis.block_hash().to_string(), "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f");
+    }
+
+    #[test]
+    fn test_connects_to() {
+        let genesis = genesis_header();
+        let block_1 = BlockHeader::parse_exact(&from_hex(BLOCK_1_HEADER).unwrap()).unwrap();
+        assert_eq!(block_1.block_hash().to_string(), "00000000839a8e6886ab5951d76f411475428afc90947ee320161bbf18eb6048");
+        assert_eq!(block_1.prev_blockhash(), genesis.block_hash());
+        assert_eq!(genesis.prev_blockhash(), BlockHash([0; 32]));
+
+        assert!(connects_to(&block_1, &genesis));
+        assert!(!connects_to(&genesis, &block_1));
+        assert!(!connects_to(&block_1, &block_1));
+    }
+
+    /// Regtest headers on top of the genesis, the nonce is ground until the PoW is valid
+    fn mine_headers(timestamps: &[u32]) -> Vec<BlockHeader> {
+        let mut headers = vec![Block::genesis(Network::Regtest).header];
+        for timestamp in timestamps {
+            let prev_block = headers.last().unwrap().block_hash().0;
+            let mut header = BlockHeader { prev_block, timestamp: *timestamp, ..headers[0].clone() };
+            while !header.pow_valid() {
+                header.nonce += 1;
+            }
+            headers.push(header);
+        }
+        headers
+    }
+
+    #[test]
+    fn test_validate_header_chain() {
+        let mainnet = [genesis_header(), BlockHeader::parse_exact(&from_hex(BLOCK_1_HEADER).unwrap()).unwrap()];
+        assert_eq!(validate_header_chain(&mainnet, true), Ok(()));
+        assert_eq!(validate_header_chain(&[], true), Ok(()));
+
+        let genesis_time = Block::genesis(Network::Regtest).header.timestamp;
+        let timestamps = (1..=12).map(|i| genesis_time + i * 600).collect::<Vec<_>>();
+        let headers = mine_headers(&timestamps);
+        assert_eq!(validate_header_chain(&headers, true), Ok(()));
+
+        let mut disconnected = headers.clone();
+        disconnected.remove(5);
+        assert_eq!(validate_header_chain(&disconnected, true), Err(ChainError::Disconnected(5)));
+
+        let mut bad_pow = headers.clone();
+        bad_pow[3].bits = 0x1d00ffff;
+        assert_eq!(validate_header_chain(&bad_pow[..4], true), Err(ChainError::InvalidPow(3)));
+        assert_eq!(validate_header_chain(&bad_pow[..4], false), Ok(()));
+
+        // Without the PoW check, headers don't have to be mined to be linked and timed
+        let mut unmined = vec![headers[0].clone()];
+        for timestamp in &timestamps {
+            let prev_block = unmined.last().unwrap().block_hash().0;
+            unmined.push(BlockHeader { prev_block, timestamp: *timestamp, bits: 0x1d00ffff, ..headers[0].clone() });
+        }
+        assert_eq!(validate_header_chain(&unmined, false), Ok(()));
+        assert_eq!(validate_header_chain(&unmined, true), Err(ChainError::InvalidPow(1)));
+        unmined.swap(3, 4);
+        assert_eq!(validate_header_chain(&unmined, false), Err(ChainError::Disconnected(3)));
+
+        // Timestamps can go backwards, but must stay above the median of the last 11
+        let mut timestamps = timestamps;
+        timestamps[11] = timestamps[5];
+        let headers = mine_headers(&timestamps);
+        assert_eq!(validate_header_chain(&headers, true), Err(ChainError::TimestampTooOld(12)));
+        timestamps[11] = timestamps[6] + 1;
+        assert_eq!(validate_header_chain(&mine_headers(&timestamps), true), Ok(()));
+    }
+
+    #[test]
+    fn test_from_str() {
+        let header = BLOCK_1_HEADER.parse::<BlockHeader>().unwrap();
+        assert_eq!(header.prev_blockhash(), genesis_header().block_hash());
+        assert_eq!(format!("{}00", BLOCK_1_HEADER).parse::<BlockHeader>(), Err(Error::TrailingBytes(1)));
+        assert_eq!(BLOCK_1_HEADER[..158].parse::<BlockHeader>(), Err(Error::InsufficientBytes { needed: 4, got: 3 }));
+
+        assert_eq!(BLOCK.parse::<Block>(), Block::from_hex_str(BLOCK));
+        assert_eq!("zz".parse::<Block>(), Err(Error::InvalidHexDigit('z')));
+    }
+
+    #[test]
+    fn test_try_from() {
+        let header_bytes = from_hex(BLOCK_1_HEADER).unwrap();
+        assert_eq!(BlockHeader::try_from(&header_bytes[..]), BLOCK_1_HEADER.parse::<BlockHeader>());
+        assert_eq!(BlockHeader::try_from(&header_bytes[..79]), Err(Error::InsufficientBytes { needed: 4, got: 3 }));
+
+        let mut block_bytes = from_hex(BLOCK).unwrap();
+        assert_eq!(Block::try_from(&block_bytes[..]), Block::from_hex_str(BLOCK));
+        block_bytes.push(0x00);
+        assert_eq!(Block::try_from(&block_bytes[..]), Err(Error::TrailingBytes(1)));
+    }
+
+    #[test]
+    fn test_header_only_parse() {
+        let block = Block::from_hex_str(BLOCK).unwrap();
+        assert_eq!(BlockHeader::from_block_hex(BLOCK), Ok(block.header.clone()));
+        assert_eq!(BlockHeader::parse_from_block_bytes(&from_hex(BLOCK.trim()).unwrap()), Ok(block.header));
+
+        // Nothing past the header is decoded, not even the hex
+        assert_eq!(BlockHeader::from_block_hex(&format!("{}zz", BLOCK_1_HEADER)).map(|header| header.nonce), Ok(0x9962e301));
+        assert_eq!(BlockHeader::from_block_hex(&BLOCK_1_HEADER[..150]), Err(Error::InsufficientBytes { needed: 4, got: 3 }));
+        assert_eq!(BlockHeader::from_block_hex(&BLOCK_1_HEADER[..151]), Err(Error::OddHexLength));
+    }
+
+    #[test]
+    fn test_validate_coinbase() {
+        let block = Block::from_hex_str(BLOCK).unwrap();
+        assert_eq!(block.validate_coinbase(), Ok(()));
+        assert_eq!(Block::genesis(Network::Mainnet).validate_coinbase(), Ok(()));
+
+        let mut modified = block.clone();
+        modified.transactions.clear();
+        assert_eq!(modified.validate_coinbase(), Err(Error::EmptyBlock));
+
+        let mut modified = block.clone();
+        modified.transactions.remove(0);
+        assert_eq!(modified.validate_coinbase(), Err(Error::MissingCoinbase));
+
+        let mut modified = block.clone();
+        let extra_input = modified.transactions[1].inputs[0].clone();
+        modified.transactions[0].inputs.push(extra_input);
+        assert_eq!(modified.validate_coinbase(), Err(Error::CoinbaseInputCount(2)));
+
+        let mut modified = block.clone();
+        modified.transactions[0].inputs[0].script_sig = Script(vec![0x51]);
+        assert_eq!(modified.validate_coinbase(), Err(Error::CoinbaseScriptSize(1)));
+        modified.transactions[0].inputs[0].script_sig = Script(vec![0x51; 101]);
+        assert_eq!(modified.validate_coinbase(), Err(Error::CoinbaseScriptSize(101)));
+
+        let mut modified = block.clone();
+        let coinbase = modified.transactions[0].clone();
+        modified.transactions.push(coinbase);
+        assert_eq!(modified.validate_coinbase(), Err(Error::ExtraCoinbase(modified.transactions.len() - 1)));
+    }
+
+    #[test]
+    fn test_check_no_duplicate_inputs() {
+        let mut block = Block::from_hex_str(BLOCK).unwrap();
+        assert_eq!(block.check_no_duplicate_inputs(), Ok(()));
+
+        // Spending an output twice would have let the transaction create twice its value
+        let tx = &mut block.transactions[2];
+        tx.inputs.push(tx.inputs[0].clone());
+        let txid = tx.txid();
+        assert_eq!(block.check_no_duplicate_inputs(), Err(Error::DuplicateInputs(txid)));
+    }
+
+    #[test]
+    fn test_genesis() {
+        let hash = |network| Block::genesis(network).header.block_hash().to_string();
+        assert_eq!(hash(Network::Mainnet), "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f");
+        assert_eq!(hash(Network::Testnet), "000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943");
+        assert_eq!(hash(Network::Regtest), "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206");
+        assert_eq!(hash(Network::Signet), "00000008819873e925422c1ff0f99f7cc9bbb232af63a077a480a3633bee1ef6");
+
+        let genesis = Block::genesis(Network::Mainnet);
+        assert!(genesis.verify_merkle_root());
+        assert_eq!(genesis.compute_merkle_root(), genesis.header.merkle_root);
+        assert_eq!(Txid(genesis.header.merkle_root).to_string(), "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b");
+        let coinbase_script = genesis.transactions[0].inputs[0].script_sig.as_bytes();
+        assert!(String::from_utf8_lossy(coinbase_script).contains("The Times 03/Jan/2009 Chancellor on brink of second bailout for banks"));
+        assert!(Block::genesis(Network::Regtest).header.pow_valid());
+    }
+
+    #[test]
+    fn test_target() {
+        let target = |bits| to_hex(&BlockHeader { bits, ..genesis_header() }.target());
+
+        assert_eq!(target(0x1d00ffff), format!("00000000ffff{}", "0".repeat(52)));
+        assert_eq!(target(0x01003456), "0".repeat(64));
+        assert_eq!(target(0x01123456), format!("{}12", "0".repeat(62)));
+        assert_eq!(target(0x02008000), format!("{}0080", "0".repeat(60)));
+        assert_eq!(target(0x05009234), format!("{}92340000", "0".repeat(56)));
+        assert_eq!(target(0x20123456), format!("123456{}", "0".repeat(58)));
+        assert_eq!(target(0x22000001), format!("01{}", "0".repeat(62)));
+        // Negative and overflowing encodings
+        assert_eq!(target(0x04923456), "0".repeat(64));
+        assert_eq!(target(0x23000001), "0".repeat(64));
+        assert_eq!(target(0xff123456), "0".repeat(64));
+    }
+
+    #[test]
+    fn test_pow_valid() {
+        let genesis = genesis_header();
+        assert!(genesis.pow_valid());
+        assert!(!BlockHeader { nonce: 0, ..genesis.clone() }.pow_valid());
+        assert!(!BlockHeader { bits: 0x04923456, ..genesis }.pow_valid());
+
+        let block = Block::from_hex_str(BLOCK).unwrap();
+        assert!(block.header.pow_valid());
+    }
+
+    #[test]
+    fn test_difficulty() {
+        let difficulty = |bits| BlockHeader { bits, ..genesis_header() }.difficulty();
+
+        assert_eq!(difficulty(0x1d00ffff), 1.0);
+        assert_eq!(difficulty(0x1c00ffff), 256.0);
+        assert_eq!(difficulty(0x1e00ffff), 1.0 / 256.0);
+
+        let block = Block::from_hex_str(BLOCK).unwrap();
+        assert_eq!(block.header.bits, 0x18015ddc);
+        assert_eq!(block.header.difficulty(), 804525194568.1318);
+    }
+
+    #[test]
+    fn test_merkle_root() {
+        let a = sha256d(b"a");
+        let b = sha256d(b"b");
+        let c = sha256d(b"c");
+
+        assert_eq!(merkle_root(vec![]), [0; 32]);
+        assert_eq!(merkle_root(vec![a]), a);
+        assert_eq!(merkle_root(vec![a, b]), merkle_parent(&a, &b));
+        assert_eq!(
+            merkle_root(vec![a, b, c]),
+            merkle_parent(&merkle_parent(&a, &b), &merkle_parent(&c, &c))
+        );
+    }
+
+    #[test]
+    fn test_op_return_payloads() {
+        let (block, _) = Block::parse(&from_hex(BLOCK).unwrap()).unwrap();
+        assert_eq!(
+            block.op_return_payloads(),
+            vec![from_hex("aa21a9edc8b1ed3c7c6ed905a3eca070d16d03c0a349db5890fa5f6c5cea03a56afb0e4b").unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_merkle_proof() {
+        let block_bytes = from_hex(BLOCK).unwrap();
+        let (block, _) = Block::parse(&block_bytes).unwrap();
+        let root = block.compute_merkle_root();
+
+        for (index, tx) in block.transactions.iter().enumerate() {
+            let mut proof = block.merkle_proof(&tx.txid()).unwrap();
+            assert_eq!(proof.index, index);
+            assert_eq!(proof.path.len(), 5);
+            assert!(verify_merkle_proof(&tx.txid(), &proof, &root));
+            proof.index ^= 1;
+            assert!(!verify_merkle_proof(&tx.txid(), &proof, &root));
+        }
+
+        assert_eq!(block.merkle_proof(&Txid([0; 32])), None);
+    }
+
+    #[test]
+    fn test_merkle_proof_odd_level() {
+        let mut block = Block::parse(&from_hex(BLOCK).unwrap()).unwrap().0;
+        block.transactions.truncate(3);
+        let root = block.compute_merkle_root();
+
+        let last = block.transactions[2].txid();
+        let proof = block.merkle_proof(&last).unwrap();
+        assert_eq!(proof.path[0], last.0);
+        assert!(verify_merkle_proof(&last, &proof, &root));
+    }
+
+    #[test]
+    fn test_verify_merkle_root() {
+        let block_bytes = from_hex(BLOCK).unwrap();
+        let (block, _) = Block::parse(&block_bytes).unwrap();
+        assert!(block.verify_merkle_root());
+    }
+
+    #[test]
+    fn test_block_roundtrip() {
+        let block_bytes = from_hex(BLOCK).unwrap();
+        let (block, _) = Block::parse(&block_bytes).unwrap();
+
+        let mut encoded = Vec::new();
+        block.encode(&mut encoded);
+        assert_bytes_eq(&encoded, &block_bytes);
+    }
+
+    #[test]
+    fn test_parse_with_limits() {
+        let block_bytes = from_hex(BLOCK).unwrap();
+        let block = Block::parse_exact(&block_bytes).unwrap();
+        let max_inputs = block.transactions.iter().map(Transaction::input_count).max().unwrap();
+        let max_outputs = block.transactions.iter().map(Transaction::output_count).max().unwrap();
+        let exact = ParseLimits {
+            varint_mode: ParseMode::Strict,
+            max_transactions: block.transactions.len(),
+            max_inputs,
+            max_outputs,
+            max_script_len: MAX_SCRIPT_SIZE,
+            // No segwit transactions
+            max_witness_items: 0,
+            max_witness_item_len: 0,
+            max_total_bytes: block_bytes.len(),
+        };
+        assert_eq!(Block::parse_with_limits(&block_bytes, &exact), Ok((block.clone(), &[][..])));
+        assert_eq!(Block::parse_with_limits(&block_bytes, &ParseLimits::default()), Ok((block.clone(), &[][..])));
+        // Only the block itself counts, like the next one in a `blk*.dat` file
+        let mut followed = block_bytes.clone();
+        followed.extend_from_slice(&[0xf9, 0xbe, 0xb4, 0xd9]);
+        assert_eq!(Block::parse_with_limits(&followed, &exact), Ok((block.clone(), &[0xf9, 0xbe, 0xb4, 0xd9][..])));
+
+        let exceeded = |limits: ParseLimits| Block::parse_with_limits(&block_bytes, &limits).err();
+        let len = block_bytes.len();
+        assert_eq!(exceeded(ParseLimits { max_total_bytes: len - 1, ..exact }), Some(Error::LimitExceeded { got: len as u64, max: len - 1 }));
+        let count = block.transactions.len();
+        assert_eq!(exceeded(ParseLimits { max_transactions: count - 1, ..exact }), Some(Error::LimitExceeded { got: count as u64, max: count - 1 }));
+        assert_eq!(exceeded(ParseLimits { max_inputs: max_inputs - 1, ..exact }), Some(Error::LimitExceeded { got: max_inputs as u64, max: max_inputs - 1 }));
+        assert_eq!(exceeded(ParseLimits { max_outputs: max_outputs - 1, ..exact }), Some(Error::LimitExceeded { got: max_outputs as u64, max: max_outputs - 1 }));
+        let coinbase_len = block.transactions[0].inputs[0].script_sig.size();
+        assert_eq!(exceeded(ParseLimits { max_script_len: 10, ..exact }), Some(Error::LimitExceeded { got: coinbase_len as u64, max: 10 }));
+    }
+
+    #[test]
+    fn test_strict_varints() {
+        let block_bytes = from_hex(BLOCK).unwrap();
+        let block = Block::parse_exact(&block_bytes).unwrap();
+        let strict = ParseLimits { varint_mode: ParseMode::Strict, ..ParseLimits::UNLIMITED };
+        assert_eq!(Block::parse_with_limits(&block_bytes, &strict), Ok((block.clone(), &[][..])));
+
+        // The transaction count as `0xfd` and two bytes
+        let count = block.transactions.len() as u8;
+        let mut non_canonical = block_bytes[..HEADER_SIZE].to_vec();
+        non_canonical.extend_from_slice(&[0xfd, count, 0x00]);
+        non_canonical.extend_from_slice(&block_bytes[HEADER_SIZE + 1..]);
+        assert_eq!(Block::parse_exact(&non_canonical), Ok(block.clone()));
+        assert_eq!(Block::parse_with_limits(&non_canonical, &strict), Err(Error::NonCanonicalVarInt(count as u64)));
+
+        // The length of the first output script, nested in a transaction
+        let mut tx_bytes = Vec::new();
+        block.transactions[1].encode(&mut tx_bytes);
+        let script_len = block.transactions[1].outputs[0].script_pubkey.size();
+        let mut block = block;
+        block.transactions.truncate(2);
+        let mut non_canonical = Vec::new();
+        block.encode(&mut non_canonical);
+        let mut inputs = Vec::new();
+        block.transactions[1].inputs.encode(&mut inputs);
+        // After the version, the inputs, the output count and the value
+        let offset = non_canonical.len() - tx_bytes.len() + 4 + inputs.len() + 1 + 8;
+        assert_eq!(non_canonical[offset], script_len as u8);
+        non_canonical.splice(offset..offset + 1, [0xfe, script_len as u8, 0x00, 0x00, 0x00]);
+        assert_eq!(Block::parse_exact(&non_canonical), Ok(block));
+        assert_eq!(Block::parse_with_limits(&non_canonical, &strict), Err(Error::NonCanonicalVarInt(script_len as u64)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_block_decode() {
+        let block_bytes = from_hex(BLOCK).unwrap();
+        let (block, _) = Block::parse(&block_bytes).unwrap();
+
+        let mut reader = &block_bytes[..];
+        assert_eq!(Block::decode(&mut reader), Ok(block));
+        assert!(reader.is_empty());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_from_hex() {
+        let block = Block::from_hex_file(concat!(env!("CARGO_MANIFEST_DIR"), "/block.hex")).unwrap();
+        assert_eq!(Block::from_hex_str(&format!("  {}\n", BLOCK.trim())), Ok(block));
+
+        assert_eq!(Block::from_hex_str(&format!("{}00", BLOCK.trim())), Err(Error::TrailingBytes(1)));
+        assert_eq!(Block::from_hex_file("/nonexistent/block.hex"), Err(Error::Io(std::io::ErrorKind::NotFound)));
+    }
+
+    #[test]
+    fn test_coinbase_height() {
+        let mut block = Block::from_hex_str(BLOCK).unwrap();
+        assert_eq!(block.coinbase_height(), Some(477105));
+
+        block.transactions[0].inputs[0].script_sig = Script(vec![0x5a, 0x00]);
+        assert_eq!(block.coinbase_height(), Some(10));
+        block.transactions[0].inputs[0].script_sig = Script(vec![0x02, 0x01, 0x00]);
+        assert_eq!(block.coinbase_height(), None);
+        block.transactions.remove(0);
+        assert_eq!(block.coinbase_height(), None);
+    }
+
+    #[test]
+    fn test_block_subsidy() {
+        assert_eq!(block_subsidy(0), 50 * 100_000_000);
+        assert_eq!(block_subsidy(209_999), 50 * 100_000_000);
+        assert_eq!(block_subsidy(210_000), 25 * 100_000_000);
+        assert_eq!(block_subsidy(630_000), 625_000_000);
+        assert_eq!(block_subsidy(840_000), 312_500_000);
+        assert_eq!(block_subsidy(6_929_999), 1);
+        assert_eq!(block_subsidy(6_930_000), 0);
+        assert_eq!(block_subsidy(64 * 210_000), 0);
+    }
+
+    #[test]
+    fn test_total_output_value() {
+        let block = Block::from_hex_str(BLOCK).unwrap();
+        let coinbase = block.transactions[0].total_output_value().unwrap();
+        assert!(coinbase >= block_subsidy(477105));
+        assert!(block.total_output_value().unwrap() > coinbase);
+    }
+
+    #[test]
+    fn test_block_size() {
+        let mut block = Block::from_hex_str(BLOCK).unwrap();
+        assert_eq!(block.size(), from_hex(BLOCK).unwrap().len());
+        assert_eq!(block.stripped_size(), block.size());
+        assert_eq!(block.weight(), block.size() * 4);
+
+        block.transactions[1].inputs[0].witness = vec![vec![0xaa; 10]];
+        // Marker, flag, one item count per input and the 10 bytes item
+        let witness_size = 2 + block.transactions[1].inputs.len() + 1 + 10;
+        assert_eq!(block.size(), block.stripped_size() + witness_size);
+        assert_eq!(block.weight(), block.stripped_size() * 4 + witness_size);
+    }
+
+    #[test]
+    fn test_outputs() {
+        let block = Block::from_hex_str(BLOCK).unwrap();
+
+        let outputs = block.outputs().collect::<Vec<_>>();
+        assert_eq!(outputs.len(), block.transactions.iter().map(|tx| tx.outputs.len()).sum::<usize>());
+        let last_tx = block.transactions.last().unwrap();
+        let (outpoint, txout) = outputs.last().unwrap();
+        assert_eq!(*outpoint, OutPoint { txid: last_tx.txid().0, vout: last_tx.outputs.len() as u32 - 1 });
+        assert_eq!(*txout, last_tx.outputs.last().unwrap());
+
+        let spent = block.spent_outpoints().collect::<Vec<_>>();
+        assert_eq!(spent.len(), block.transactions[1..].iter().map(|tx| tx.inputs.len()).sum::<usize>());
+        assert!(spent.iter().all(|outpoint| !outpoint.is_coinbase()));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_apply_to_utxos() {
+        let block = Block::from_hex_str(BLOCK).unwrap();
+        let spent = block.spent_outpoints().cloned().collect::<Vec<_>>();
+        let created = block.outputs().filter(|(_, txout)| !txout.script_pubkey.is_unspendable()).collect::<HashMap<_, _>>();
+
+        let mut utxos = spent.iter().map(|outpoint| (outpoint.clone(), TxOut { value: 1, script_pubkey: Script(vec![]) })).collect::<HashMap<_, _>>();
+        let unrelated = OutPoint { txid: [0xaa; 32], vout: 0 };
+        utxos.insert(unrelated.clone(), TxOut { value: 2, script_pubkey: Script(vec![]) });
+        block.apply_to_utxos(&mut utxos);
+
+        assert!(utxos.contains_key(&unrelated));
+        assert!(spent.iter().all(|outpoint| !utxos.contains_key(outpoint)));
+        let spent_in_block = spent.iter().filter(|outpoint| created.contains_key(outpoint)).count();
+        assert_eq!(utxos.len(), 1 + created.len() - spent_in_block);
+        assert!(block.outputs().any(|(_, txout)| txout.script_pubkey.is_unspendable()));
+        assert!(utxos.values().all(|txout| !txout.script_pubkey.is_unspendable()));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_total_fees() {
+        let block = Block::from_hex_str(BLOCK).unwrap();
+        // Each transaction pays a fee of 1 sat per input on top of its outputs, all on the first input
+        let mut values = HashMap::new();
+        for tx in &block.transactions[1..] {
+            let output_value = tx.total_output_value().unwrap();
+            for (index, txin) in tx.inputs.iter().enumerate() {
+                values.insert(txin.previous_output.clone(), if index == 0 { output_value + tx.inputs.len() as u64 } else { 0 });
+            }
+        }
+        let inputs = block.transactions[1..].iter().map(|tx| tx.inputs.len() as u64).sum::<u64>();
+        assert_eq!(block.total_fees(|outpoint| values.get(outpoint).copied()), Ok(inputs));
+
+        let missing = block.transactions[1].inputs[0].previous_output.clone();
+        values.remove(&missing);
+        assert_eq!(block.total_fees(|outpoint| values.get(outpoint).copied()), Err(Error::UnknownPrevout(missing)));
+        assert_eq!(Block::genesis(Network::Mainnet).total_fees(|_| None), Ok(0));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let block = Block::from_hex_str(BLOCK).unwrap();
+        let json = serde_json::to_value(&block).unwrap();
+
+        assert_eq!(json["previousblockhash"], block.header.prev_block.iter().rev().map(|b| format!("{:02x}", b)).collect::<String>());
+        assert_eq!(json["tx"][0]["vin"][0]["coinbase"].as_str().unwrap()[..8], *"03b14707");
+        assert!(json["tx"][0]["vin"][0].get("scriptSig").is_none());
+        assert_eq!(json["tx"][1]["vout"][0]["scriptPubKey"]["asm"], block.transactions[1].outputs[0].script_pubkey.to_asm());
+        assert_eq!(json["tx"][1]["vin"][0]["txid"], Txid(block.transactions[1].inputs[0].previous_output.txid).to_string());
+
+        assert_eq!(serde_json::from_value::<Block>(json).unwrap(), block);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_parse_block_file() {
+        const MAGIC: [u8; 4] = [0xf9, 0xbe, 0xb4, 0xd9];
+        assert_eq!(crate::Network::Mainnet.magic(), MAGIC);
+
+        let block_bytes = from_hex(BLOCK).unwrap();
+        let block = Block::parse_exact(&block_bytes).unwrap();
+        let mut file = Vec::new();
+        for _ in 0..2 {
+            file.extend_from_slice(&MAGIC);
+            (block_bytes.len() as u32).encode(&mut file);
+            file.extend_from_slice(&block_bytes);
+        }
+
+        let blocks = parse_block_file(&mut &file[..], MAGIC).collect::<Vec<_>>();
+        assert_eq!(blocks, vec![Ok(block.clone()), Ok(block.clone())]);
+
+        // Zero padding at the end of the file
+        let mut padded = file.clone();
+        padded.extend_from_slice(&[0; 16]);
+        assert_eq!(parse_block_file(&mut &padded[..], MAGIC).count(), 2);
+
+        let blocks = parse_block_file(&mut &file[..], crate::Network::Testnet.magic()).collect::<Vec<_>>();
+        assert_eq!(blocks, vec![Err(Error::InvalidMagic(MAGIC))]);
+
+        let truncated = &file[..file.len() - 1];
+        let blocks = parse_block_file(&mut &truncated[..], MAGIC).collect::<Vec<_>>();
+        assert_eq!(blocks, vec![Ok(block), Err(Error::Io(std::io::ErrorKind::UnexpectedEof))]);
+    }
+
+    #[test]
+    fn test_format_utc() {
+        assert_eq!(format_utc(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_utc(1231006505), "2009-01-03 18:15:05 UTC");
+        assert_eq!(format_utc(951782400), "2000-02-29 00:00:00 UTC");
+        assert_eq!(format_utc(u32::MAX), "2106-02-07 06:28:15 UTC");
+
+        assert_eq!(genesis_header().time(), "2009-01-03 18:15:05 UTC");
+    }
+
+    #[test]
+    fn test_into_iter() {
+        let block = Block::from_hex_str(BLOCK).unwrap();
+        let mut count = 0;
+        for tx in &block {
+            assert_eq!(tx, &block.transactions[count]);
+            count += 1;
+        }
+        assert_eq!(count, block.transactions.len());
+
+        let txids = block.transactions.iter().map(|tx| tx.txid()).collect::<Vec<_>>();
+        assert_eq!(block.into_iter().map(|tx| tx.txid()).collect::<Vec<_>>(), txids);
+    }
+
+    #[test]
+    fn test_display() {
+        let block = Block::from_hex_str(BLOCK).unwrap();
+        let display = block.to_string();
+        let lines = display.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines[0], "Block 00000000000000000043eb92a914f1dcd3e392f68ecbc89fdf20a6544eb8a516");
+        assert_eq!(lines[4], "  time:         2017-07-23 03:19:12 UTC");
+        assert_eq!(lines[7], "  transactions: 32");
+        assert_eq!(lines.len(), 8 + 32);
+        assert!(lines[8].starts_with("    ff8249fb7c3079d30392db6401c912a53f98de9a09634893fc81b3368b74e35b   1 in "));
+    }
+
+    #[test]
+    fn test_witness_commitment() {
+        let mut block = Block::from_hex_str(BLOCK).unwrap();
+        let commitment = block.witness_commitment().unwrap();
+        assert_eq!(to_hex(&commitment), "c8b1ed3c7c6ed905a3eca070d16d03c0a349db5890fa5f6c5cea03a56afb0e4b");
+
+        // Mined before segwit activated, so the coinbase doesn't carry the reserved value yet
+        assert!(!block.verify_witness_commitment());
+        block.transactions[0].inputs[0].witness = vec![vec![0; 32]];
+        assert!(block.verify_witness_commitment());
+
+        block.transactions.swap(1, 2);
+        assert!(!block.verify_witness_commitment());
+
+        let genesis = Block::genesis(Network::Mainnet);
+        assert_eq!(genesis.witness_commitment(), None);
+        assert!(genesis.verify_witness_commitment());
+    }
+
+    #[test]
+    fn test_txids() {
+        let block = Block::from_hex_str(BLOCK).unwrap();
+        let txids = block.txids();
+        assert_eq!(txids.len(), block.transactions.len());
+        assert_eq!(merkle_root(txids.iter().map(|txid| txid.0).collect()), block.header.merkle_root);
+
+        let wtxids = block.wtxids();
+        assert_eq!(wtxids[0], Txid([0; 32]));
+        assert_eq!(wtxids[1..], txids[1..]);
+
+        let mut segwit = block.clone();
+        segwit.transactions[1].inputs[0].witness = vec![vec![0x01]];
+        assert_ne!(segwit.wtxids()[1], txids[1]);
+        assert_eq!(segwit.wtxids()[1], segwit.transactions[1].wtxid());
+    }
+
+    #[test]
+    fn test_short_ids() {
+        let block = Block::from_hex_str(BLOCK).unwrap();
+        let short_ids = block.short_ids(42);
+        assert_eq!(short_ids.len(), block.transactions.len());
+        assert!(short_ids.iter().all(|id| *id >> 48 == 0));
+        assert_eq!(block.short_ids(42), short_ids);
+        assert_ne!(block.short_ids(43), short_ids);
+
+        // Compact block of a regtest block with nonce 18053200567810711460, from Elements. The
+        // coinbase is prefilled, the other transaction has short id 0a6979e97145
+        let block = Block::from_hex_str("000000206c750a364035aefd5f81508a08769975116d9195312ee4520dceac39e1fdc62c4dc67473b8e354358c1e610afeaff7410858bd45df43e2940f8a62bd3d5e3ac943c2975cffff7f200000000002020000000001010000000000000000000000000000000000000000000000000000000000000000ffffffff04016b0101ffffffff020006062a0100000001510000000000000000266a24aa21a9ed4a3d9f3343dafcc0d6f6d4310f2ee5ce273ed34edca6c75db3a73e7f368734200120000000000000000000000000000000000000000000000000000000000000000000000000020000000001021fc20ba2bd745507b8e00679e3b362558f9457db374ca28ffa5243f4c23a4d5f00000000171600147c9dea14ffbcaec4b575e03f05ceb7a81cd3fcbffdffffff915d689be87b43337f42e26033df59807b768223368f189a023d0242d837768900000000171600147c9dea14ffbcaec4b575e03f05ceb7a81cd3fcbffdffffff0200cdf5050000000017a9146803c72d9154a6a20f404bed6d3dcee07986235a8700e1f5050000000017a9144e6a4c7cb5b5562904843bdf816342f4db9f5797870247304402205e9bf6e70eb0e4b495bf483fd8e6e02da64900f290ef8aaa64bb32600d973c450220670896f5d0e5f33473e5f399ab680cc1d25c2d2afd15abd722f04978f28be887012103e4e4d9312b2261af508b367d8ba9be4f01b61d6d6e78bec499845b4f410bcf2702473044022045ac80596a6ac9c8c572f94708709adaf106677221122e08daf8b9741a04f66a022003ccd52a3b78f8fd08058fc04fc0cffa5f4c196c84eae9e37e2a85babe731b57012103e4e4d9312b2261af508b367d8ba9be4f01b61d6d6e78bec499845b4f410bcf276a000000").unwrap();
+        assert_eq!(block.short_ids(18053200567810711460)[1], 0x4571e979690a);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_parallel_merkle_root() {
+        let block = Block::from_hex_str(BLOCK).unwrap();
+        let serial = merkle_root(block.transactions.iter().map(|tx| tx.txid().0).collect());
+        assert_eq!(block.compute_merkle_root(), serial);
+        assert_eq!(serial, block.header.merkle_root);
+    }
+}
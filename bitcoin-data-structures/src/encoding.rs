@@ -0,0 +1,478 @@
+#[cfg(feature = "std")]
+use std::io::{self, Read};
+
+use crate::prelude::*;
+use crate::utils::*;
+
+pub trait Parse: Sized {
+    fn parse(bytes: &[u8]) -> Result<(Self, &[u8]), Error>;
+
+    /// Like `parse`, but fails with `Error::TrailingBytes` unless all the bytes are consumed
+    fn parse_exact(bytes: &[u8]) -> Result<Self, Error> {
+        let (item, remainder) = Self::parse(bytes)?;
+        if !remainder.is_empty() {
+            return Err(Error::TrailingBytes(remainder.len()));
+        }
+
+        Ok(item)
+    }
+}
+
+/// Streaming counterpart of `Parse`, reads exactly the bytes of one item from `r`. Length
+/// prefixes are read leniently, `parse_with_limits` can reject the non-canonical ones
+#[cfg(feature = "std")]
+pub trait Decode: Sized {
+    fn decode<R: Read>(r: &mut R) -> Result<Self, Error>;
+}
+
+pub trait Encode {
+    fn encode(&self, out: &mut Vec<u8>);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VarInt(pub u64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParseMode {
+    /// Accept any encoding that decodes to a value
+    Lenient,
+    /// Only accept the canonical encodings required by consensus
+    Strict,
+}
+
+impl VarInt {
+    pub fn parse_with_mode(bytes: &[u8], mode: ParseMode) -> Result<(Self, &[u8]), Error> {
+        check_len(bytes, 1)?;
+        let len = match bytes[0] {
+            ..=0xFC => 1,
+            0xFD => 3,
+            0xFE => 5,
+            0xFF => 9,
+        };
+        check_len(bytes, len)?;
+
+        let (val, remainder) = match bytes[0] {
+            ..=0xFC => (bytes[0] as u64, &bytes[1..]),
+            0xFD => take_bytes(&bytes[1..]).map(|(v, b)| (u16::from_le_bytes(v) as u64, b))?,
+            0xFE => take_bytes(&bytes[1..]).map(|(v, b)| (u32::from_le_bytes(v) as u64, b))?,
+            0xFF => take_bytes(&bytes[1..]).map(|(v, b)| (u64::from_le_bytes(v), b))?,
+        };
+
+        let canonical = match len {
+            3 => val >= 0xFD,
+            5 => val > 0xFFFF,
+            9 => val > 0xFFFFFFFF,
+            _ => true,
+        };
+        if mode == ParseMode::Strict && !canonical {
+            return Err(Error::NonCanonicalVarInt(val));
+        }
+
+        Ok((VarInt(val), remainder))
+    }
+}
+
+impl VarInt {
+    /// Length of the minimal encoding: 1, 3, 5 or 9 bytes
+    pub fn encoded_len(&self) -> usize {
+        match self.0 {
+            ..=0xFC => 1,
+            0xFD..=0xFFFF => 3,
+            0x10000..=0xFFFFFFFF => 5,
+            _ => 9,
+        }
+    }
+}
+
+impl From<u64> for VarInt {
+    fn from(val: u64) -> Self {
+        VarInt(val)
+    }
+}
+
+impl Parse for VarInt {
+    fn parse(bytes: &[u8]) -> Result<(Self, &[u8]), Error> {
+        VarInt::parse_with_mode(bytes, ParseMode::Lenient)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Decode for VarInt {
+    fn decode<R: Read>(r: &mut R) -> Result<Self, Error> {
+        let val = match u8::decode(r)? {
+            0xFD => u16::from_le_bytes(read_bytes(r)?) as u64,
+            0xFE => u32::from_le_bytes(read_bytes(r)?) as u64,
+            0xFF => u64::from_le_bytes(read_bytes(r)?),
+            prefix => prefix as u64,
+        };
+
+        Ok(VarInt(val))
+    }
+}
+
+impl Encode for VarInt {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self.0 {
+            ..=0xFC => out.push(self.0 as u8),
+            0xFD..=0xFFFF => {
+                out.push(0xFD);
+                out.extend_from_slice(&(self.0 as u16).to_le_bytes());
+            },
+            0x10000..=0xFFFFFFFF => {
+                out.push(0xFE);
+                out.extend_from_slice(&(self.0 as u32).to_le_bytes());
+            },
+            _ => {
+                out.push(0xFF);
+                out.extend_from_slice(&self.0.to_le_bytes());
+            },
+        }
+    }
+}
+
+impl Parse for i32 {
+    fn parse(bytes: &[u8]) -> Result<(Self, &[u8]), Error> {
+        let (val, bytes) = take_bytes(bytes)?;
+        Ok((i32::from_le_bytes(val), bytes))
+    }
+}
+impl Parse for u32 {
+    fn parse(bytes: &[u8]) -> Result<(Self, &[u8]), Error> {
+        let (val, bytes) = take_bytes(bytes)?;
+        Ok((u32::from_le_bytes(val), bytes))
+    }
+}
+impl Parse for u8 {
+    fn parse(bytes: &[u8]) -> Result<(Self, &[u8]), Error> {
+        let ([val], bytes) = take_bytes(bytes)?;
+        Ok((val, bytes))
+    }
+}
+impl Parse for u64 {
+    fn parse(bytes: &[u8]) -> Result<(Self, &[u8]), Error> {
+        let (val, bytes) = take_bytes(bytes)?;
+        Ok((u64::from_le_bytes(val), bytes))
+    }
+}
+
+impl Parse for [u8; 32] {
+    fn parse(bytes: &[u8]) -> Result<(Self, &[u8]), Error> {
+        take_bytes(bytes)
+    }
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn read_bytes<const N: usize, R: Read>(r: &mut R) -> Result<[u8; N], Error> {
+    let mut buf = [0; N];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Reads a length-prefixed byte vector without trusting the length for the allocation
+#[cfg(feature = "std")]
+pub(crate) fn read_var_bytes<R: Read>(r: &mut R) -> Result<Vec<u8>, Error> {
+    let len = VarInt::decode(r)?.0;
+    read_vec(r, len)
+}
+
+/// Reads exactly `len` bytes, growing the buffer as data actually arrives
+#[cfg(feature = "std")]
+pub(crate) fn read_vec<R: Read>(r: &mut R, len: u64) -> Result<Vec<u8>, Error> {
+    let mut buf = Vec::new();
+    r.by_ref().take(len).read_to_end(&mut buf)?;
+    if (buf.len() as u64) < len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+
+    Ok(buf)
+}
+
+#[cfg(feature = "std")]
+impl Decode for i32 {
+    fn decode<R: Read>(r: &mut R) -> Result<Self, Error> {
+        Ok(i32::from_le_bytes(read_bytes(r)?))
+    }
+}
+#[cfg(feature = "std")]
+impl Decode for u32 {
+    fn decode<R: Read>(r: &mut R) -> Result<Self, Error> {
+        Ok(u32::from_le_bytes(read_bytes(r)?))
+    }
+}
+#[cfg(feature = "std")]
+impl Decode for u8 {
+    fn decode<R: Read>(r: &mut R) -> Result<Self, Error> {
+        let [val] = read_bytes(r)?;
+        Ok(val)
+    }
+}
+#[cfg(feature = "std")]
+impl Decode for u64 {
+    fn decode<R: Read>(r: &mut R) -> Result<Self, Error> {
+        Ok(u64::from_le_bytes(read_bytes(r)?))
+    }
+}
+
+#[cfg(feature = "std")]
+impl Decode for [u8; 32] {
+    fn decode<R: Read>(r: &mut R) -> Result<Self, Error> {
+        read_bytes(r)
+    }
+}
+
+impl Encode for i32 {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+}
+impl Encode for u32 {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+}
+impl Encode for u8 {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(*self);
+    }
+}
+impl Encode for u64 {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl Encode for [u8; 32] {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self);
+    }
+}
+
+/// Upper bound on the capacity reserved from an untrusted length prefix, the vector still
+/// grows past it if the items are actually there
+pub(crate) const MAX_VEC_PREALLOC: u64 = 4096;
+
+/// Bounds on what `Block::parse_with_limits` accepts, for data coming from untrusted peers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ParseLimits {
+    /// Applies to every length prefix, `Strict` rejects the non-canonical ones like consensus
+    pub varint_mode: ParseMode,
+    pub max_transactions: usize,
+    /// Per transaction
+    pub max_inputs: usize,
+    /// Per transaction
+    pub max_outputs: usize,
+    /// Applies to the scriptSig of every input and scriptPubKey of every output
+    pub max_script_len: usize,
+    /// Per input
+    pub max_witness_items: usize,
+    pub max_witness_item_len: usize,
+    /// Size of the whole serialized block, nothing past it is read
+    pub max_total_bytes: usize,
+}
+
+impl ParseLimits {
+    /// No limits at all, what plain `parse` uses
+    pub const UNLIMITED: ParseLimits = ParseLimits {
+        varint_mode: ParseMode::Lenient,
+        max_transactions: usize::MAX,
+        max_inputs: usize::MAX,
+        max_outputs: usize::MAX,
+        max_script_len: usize::MAX,
+        max_witness_items: usize::MAX,
+        max_witness_item_len: usize::MAX,
+        max_total_bytes: usize::MAX,
+    };
+}
+
+impl Default for ParseLimits {
+    /// The consensus maximums implied by the 4M weight limit, so no valid block is rejected:
+    /// non-witness bytes weigh 4 and the smallest transactions, inputs and outputs are 60, 41
+    /// and 9 bytes. Lowering `max_script_len` to `MAX_SCRIPT_SIZE` is a policy choice, larger
+    /// scripts are valid in outputs but can never be spent
+    fn default() -> Self {
+        ParseLimits {
+            varint_mode: ParseMode::Strict,
+            max_transactions: 4_000_000 / (60 * 4),
+            max_inputs: 4_000_000 / (41 * 4),
+            max_outputs: 4_000_000 / (9 * 4),
+            max_script_len: 4_000_000 / 4,
+            max_witness_items: 4_000_000,
+            max_witness_item_len: 4_000_000,
+            max_total_bytes: 4_000_000,
+        }
+    }
+}
+
+/// Reads a length prefix, failing with `Error::LimitExceeded` if it's above `max_len`
+pub(crate) fn parse_len_with_limit(bytes: &[u8], max_len: usize, mode: ParseMode) -> Result<(usize, &[u8]), Error> {
+    let (len, bytes) = VarInt::parse_with_mode(bytes, mode)?;
+    if len.0 > max_len as u64 {
+        return Err(Error::LimitExceeded { got: len.0, max: max_len });
+    }
+
+    Ok((len.0 as usize, bytes))
+}
+
+/// Reads a length-prefixed byte vector with a single copy, checking `max_len` first
+pub(crate) fn parse_bytes_with_limit(bytes: &[u8], max_len: usize, mode: ParseMode) -> Result<(Vec<u8>, &[u8]), Error> {
+    let (len, bytes) = parse_len_with_limit(bytes, max_len, mode)?;
+    check_len(bytes, len)?;
+    let (data, bytes) = bytes.split_at(len);
+
+    Ok((data.to_vec(), bytes))
+}
+
+/// Like `parse_vec_with_limit`, parsing the items with `parse_item`
+pub(crate) fn parse_vec_with<'a, T>(
+    bytes: &'a [u8],
+    max_len: usize,
+    mode: ParseMode,
+    mut parse_item: impl FnMut(&'a [u8]) -> Result<(T, &'a [u8]), Error>,
+) -> Result<(Vec<T>, &'a [u8]), Error> {
+    let (len, mut bytes) = parse_len_with_limit(bytes, max_len, mode)?;
+    let mut data = Vec::with_capacity(len.min(MAX_VEC_PREALLOC as usize));
+    for _ in 0..len {
+        let (item, remainder) = parse_item(bytes)?;
+        data.push(item);
+        bytes = remainder;
+    }
+
+    Ok((data, bytes))
+}
+
+/// Like `Vec<T>::parse`, but fails with `Error::LimitExceeded` before parsing any item if the
+/// length prefix is above `max_len`
+pub fn parse_vec_with_limit<T: Parse>(bytes: &[u8], max_len: usize) -> Result<(Vec<T>, &[u8]), Error> {
+    parse_vec_with(bytes, max_len, ParseMode::Lenient, T::parse)
+}
+
+impl<T: Parse> Parse for Vec<T> {
+    fn parse(bytes: &[u8]) -> Result<(Self, &[u8]), Error> {
+        parse_vec_with_limit(bytes, usize::MAX)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Decode> Decode for Vec<T> {
+    fn decode<R: Read>(r: &mut R) -> Result<Self, Error> {
+        let len = VarInt::decode(r)?;
+        let mut data = Vec::with_capacity(len.0.min(MAX_VEC_PREALLOC) as usize);
+        for _ in 0..len.0 {
+            data.push(T::decode(r)?);
+        }
+
+        Ok(data)
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        VarInt(self.len() as u64).encode(out);
+        for item in self {
+            item.encode(out);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{OpCode, Script};
+
+    #[test]
+    fn test_parse_insufficient_bytes() {
+        fn insufficient<T: Parse>(bytes: &[u8]) -> Error {
+            T::parse(bytes).err().expect("parsing should fail")
+        }
+
+        assert_eq!(insufficient::<u8>(&[]), Error::InsufficientBytes { needed: 1, got: 0 });
+        assert_eq!(insufficient::<u32>(&[0x00, 0x01]), Error::InsufficientBytes { needed: 4, got: 2 });
+        assert_eq!(insufficient::<[u8; 32]>(&[0x00; 31]), Error::InsufficientBytes { needed: 32, got: 31 });
+        assert_eq!(insufficient::<VarInt>(&[0xFF, 0x00, 0x00]), Error::InsufficientBytes { needed: 9, got: 3 });
+        assert_eq!(insufficient::<Script>(&[0x02, 0x4c]), Error::InsufficientBytes { needed: 2, got: 1 });
+        assert_eq!(insufficient::<OpCode>(&[0x05, 0xaa, 0xbb]), Error::InsufficientBytes { needed: 6, got: 3 });
+    }
+
+    #[test]
+    fn test_parse_exact() {
+        assert_eq!(u32::parse_exact(&[0x01, 0x00, 0x00, 0x00]), Ok(1));
+        assert_eq!(u32::parse_exact(&[0x01, 0x00, 0x00, 0x00, 0xAA, 0xBB]), Err(Error::TrailingBytes(2)));
+        assert_eq!(u32::parse_exact(&[0x01]), Err(Error::InsufficientBytes { needed: 4, got: 1 }));
+    }
+
+    #[test]
+    fn test_varint_canonical() {
+        let parse = |bytes: &[u8], mode| VarInt::parse_with_mode(bytes, mode).map(|(v, _)| v.0);
+
+        assert_eq!(parse(&[0xFD, 0x0A, 0x00], ParseMode::Lenient), Ok(10));
+        assert_eq!(parse(&[0xFD, 0x0A, 0x00], ParseMode::Strict), Err(Error::NonCanonicalVarInt(10)));
+        assert_eq!(parse(&[0xFD, 0xFD, 0x00], ParseMode::Strict), Ok(0xFD));
+        assert_eq!(parse(&[0xFE, 0xFF, 0xFF, 0x00, 0x00], ParseMode::Strict), Err(Error::NonCanonicalVarInt(0xFFFF)));
+        assert_eq!(parse(&[0xFE, 0x00, 0x00, 0x01, 0x00], ParseMode::Strict), Ok(0x10000));
+        assert_eq!(parse(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00], ParseMode::Strict), Err(Error::NonCanonicalVarInt(0xFFFFFFFF)));
+    }
+
+    #[test]
+    fn test_varint_encode() {
+        fn encode(val: u64) -> Vec<u8> {
+            let mut out = Vec::new();
+            VarInt(val).encode(&mut out);
+            out
+        }
+
+        assert_eq!(encode(0xFC), vec![0xFC]);
+        assert_eq!(encode(0xFD), vec![0xFD, 0xFD, 0x00]);
+        assert_eq!(encode(0xFFFF), vec![0xFD, 0xFF, 0xFF]);
+        assert_eq!(encode(0x10000), vec![0xFE, 0x00, 0x00, 0x01, 0x00]);
+        assert_eq!(encode(0x100000000), vec![0xFF, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_varint_encoded_len() {
+        for val in [0, 0xFC, 0xFD, 0xFFFF, 0x10000, 0xFFFFFFFF, 0x100000000, u64::MAX] {
+            let mut out = Vec::new();
+            VarInt(val).encode(&mut out);
+            assert_eq!(VarInt::from(val).encoded_len(), out.len(), "length of {:#x}", val);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_varint_decode() {
+        let bytes = [0xFD, 0x0A, 0x00, 0xAA];
+        let mut reader = &bytes[..];
+        assert_eq!(VarInt::decode(&mut reader), Ok(VarInt(10)));
+        assert_eq!(reader, &[0xAA]);
+
+        let mut reader = &[0xFE, 0x00, 0x00][..];
+        assert_eq!(VarInt::decode(&mut reader), Err(Error::Io(io::ErrorKind::UnexpectedEof)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_read_var_bytes() {
+        let mut reader = &[0x02, 0xAA, 0xBB, 0xCC][..];
+        assert_eq!(read_var_bytes(&mut reader), Ok(vec![0xAA, 0xBB]));
+        assert_eq!(reader, &[0xCC]);
+
+        // A huge length must not be allocated up front
+        let mut reader = &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xAA][..];
+        assert_eq!(read_var_bytes(&mut reader), Err(Error::Io(io::ErrorKind::UnexpectedEof)));
+    }
+
+    #[test]
+    fn test_vec_huge_length() {
+        let bytes = [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01];
+        assert_eq!(Vec::<u64>::parse(&bytes), Err(Error::InsufficientBytes { needed: 8, got: 1 }));
+        #[cfg(feature = "std")]
+        assert_eq!(Vec::<u64>::decode(&mut &bytes[..]), Err(Error::Io(io::ErrorKind::UnexpectedEof)));
+        assert_eq!(parse_vec_with_limit::<u64>(&bytes, 1000), Err(Error::LimitExceeded { got: u64::MAX, max: 1000 }));
+    }
+
+    #[test]
+    fn test_parse_vec_with_limit() {
+        let bytes = [0x02, 0x01, 0x02, 0xFF];
+        assert_eq!(parse_vec_with_limit::<u8>(&bytes, 2), Ok((vec![0x01, 0x02], &[0xFF][..])));
+        assert_eq!(parse_vec_with_limit::<u8>(&bytes, 1), Err(Error::LimitExceeded { got: 2, max: 1 }));
+    }
+}
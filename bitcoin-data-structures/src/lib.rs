@@ -0,0 +1,35 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+mod amount;
+pub mod base58;
+pub mod bech32;
+mod block;
+mod encoding;
+pub mod hashes;
+mod network;
+mod prelude;
+mod script;
+pub mod script_num;
+#[cfg(feature = "serde")]
+mod serde_utils;
+mod transaction;
+pub mod utils;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+pub use amount::Amount;
+#[cfg(feature = "std")]
+pub use block::parse_block_file;
+pub use block::{block_subsidy, connects_to, validate_header_chain, verify_merkle_proof, Block, BlockHeader, ChainError, MerkleProof, MEDIAN_TIME_SPAN};
+#[cfg(feature = "std")]
+pub use encoding::Decode;
+pub use encoding::{parse_vec_with_limit, Encode, Parse, ParseLimits, ParseMode, VarInt};
+pub use hashes::{BlockHash, Txid};
+pub use network::{parse_block_message, Network};
+pub use script::{OpCode, OpCodeRef, Script, ScriptBuilder, ScriptContext, ScriptRef, ScriptType, SmallNum, UnknownOpCode, MAX_PUSH_SIZE, MAX_SCRIPT_SIZE, MAX_STANDARD_P2WSH_SCRIPT_SIZE};
+pub use transaction::{LockTime, NonStandardReason, OutPoint, RelativeLockTime, SighashCache, Transaction, TxIn, TxOut, TxSummary};
+pub use utils::{describe_parse_error, from_hex, from_hex_relaxed, hexdump, to_hex, to_hex_upper, to_hex_with_case, Error};
+#[cfg(feature = "wasm")]
+pub use wasm::parse_block_json;
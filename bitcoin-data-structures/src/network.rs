@@ -0,0 +1,117 @@
+use crate::block::Block;
+use crate::encoding::*;
+use crate::hashes::sha256d;
+use crate::utils::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Regtest,
+    Signet,
+}
+
+impl Network {
+    pub const MAINNET_MAGIC: u32 = 0xD9B4BEF9;
+    pub const TESTNET_MAGIC: u32 = 0x0709110B;
+    pub const REGTEST_MAGIC: u32 = 0xDAB5BFFA;
+    pub const SIGNET_MAGIC: u32 = 0x40CF030A;
+
+    /// Message start bytes, in the order they appear on the wire and in `blk*.dat` files
+    pub fn magic(&self) -> [u8; 4] {
+        let magic = match self {
+            Network::Mainnet => Network::MAINNET_MAGIC,
+            Network::Testnet => Network::TESTNET_MAGIC,
+            Network::Regtest => Network::REGTEST_MAGIC,
+            Network::Signet => Network::SIGNET_MAGIC,
+        };
+        magic.to_le_bytes()
+    }
+
+    pub fn p2pkh_prefix(&self) -> u8 {
+        match self {
+            Network::Mainnet => 0x00,
+            Network::Testnet | Network::Regtest | Network::Signet => 0x6f,
+        }
+    }
+
+    pub fn p2sh_prefix(&self) -> u8 {
+        match self {
+            Network::Mainnet => 0x05,
+            Network::Testnet | Network::Regtest | Network::Signet => 0xc4,
+        }
+    }
+
+    pub fn bech32_hrp(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "bc",
+            Network::Testnet | Network::Signet => "tb",
+            Network::Regtest => "bcrt",
+        }
+    }
+}
+
+/// Parses a P2P `block` message: magic, null-padded command, payload length and checksum,
+/// followed by the serialized block
+pub fn parse_block_message(bytes: &[u8], network: Network) -> Result<(Block, &[u8]), Error> {
+    let (magic, bytes) = take_bytes::<4>(bytes)?;
+    if magic != network.magic() {
+        return Err(Error::InvalidMagic(magic));
+    }
+    let (command, bytes) = take_bytes::<12>(bytes)?;
+    if command != *b"block\0\0\0\0\0\0\0" {
+        let name = command.iter().take_while(|b| **b != 0).map(|b| *b as char).collect();
+        return Err(Error::UnexpectedCommand(name));
+    }
+    let (len, bytes) = u32::parse(bytes)?;
+    let (checksum, bytes) = take_bytes::<4>(bytes)?;
+
+    check_len(bytes, len as usize)?;
+    let (payload, bytes) = bytes.split_at(len as usize);
+    if sha256d(payload)[..4] != checksum {
+        return Err(Error::InvalidChecksum);
+    }
+
+    Ok((Block::parse_exact(payload)?, bytes))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::*;
+
+    fn block_message(command: &[u8; 12], payload: &[u8]) -> Vec<u8> {
+        let mut message = Network::Mainnet.magic().to_vec();
+        message.extend_from_slice(command);
+        (payload.len() as u32).encode(&mut message);
+        message.extend_from_slice(&sha256d(payload)[..4]);
+        message.extend_from_slice(payload);
+        message
+    }
+
+    #[test]
+    fn test_magic() {
+        assert_eq!(Network::Mainnet.magic(), [0xf9, 0xbe, 0xb4, 0xd9]);
+        assert_eq!(Network::Testnet.magic(), [0x0b, 0x11, 0x09, 0x07]);
+        assert_eq!(Network::Regtest.magic(), [0xfa, 0xbf, 0xb5, 0xda]);
+        assert_eq!(Network::Signet.magic(), [0x0a, 0x03, 0xcf, 0x40]);
+    }
+
+    #[test]
+    fn test_parse_block_message() {
+        let payload = from_hex(include_str!("../block.hex").trim()).unwrap();
+        let block = Block::parse_exact(&payload).unwrap();
+
+        let mut message = block_message(b"block\0\0\0\0\0\0\0", &payload);
+        message.push(0xaa);
+        assert_eq!(parse_block_message(&message, Network::Mainnet), Ok((block, &[0xaa][..])));
+        assert_eq!(parse_block_message(&message, Network::Testnet), Err(Error::InvalidMagic(Network::Mainnet.magic())));
+
+        let message = block_message(b"tx\0\0\0\0\0\0\0\0\0\0", &payload);
+        assert_eq!(parse_block_message(&message, Network::Mainnet), Err(Error::UnexpectedCommand("tx".into())));
+
+        let mut message = block_message(b"block\0\0\0\0\0\0\0", &payload);
+        *message.last_mut().unwrap() ^= 0x01;
+        assert_eq!(parse_block_message(&message, Network::Mainnet), Err(Error::InvalidChecksum));
+    }
+}
@@ -0,0 +1,5 @@
+// `alloc` types that `std` would otherwise bring in scope, so that every module builds the
+// same way with and without the `std` feature
+pub(crate) use alloc::string::{String, ToString};
+pub(crate) use alloc::vec::Vec;
+pub(crate) use alloc::{format, vec};
@@ -0,0 +1,1460 @@
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io::Read;
+
+use crate::encoding::*;
+use crate::hashes::hash160;
+use crate::network::Network;
+use crate::prelude::*;
+use crate::transaction::{Transaction, TxIn};
+use crate::utils::*;
+use crate::{base58, bech32, script_num};
+
+macro_rules! opcodes {
+    ($($variant:ident = $byte:literal => $name:literal,)*) => {
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        pub enum OpCode {
+            $($variant,)*
+            /// `OP_1` through `OP_16`, see `OpCode::num`
+            Num(SmallNum),
+            Push(Vec<u8>),
+            /// Any byte not assigned to an opcode (`0xbb..=0xff`)
+            Unknown(UnknownOpCode),
+        }
+
+        impl OpCode {
+            pub fn from_byte(byte: u8) -> Option<Self> {
+                match byte {
+                    $($byte => Some(OpCode::$variant),)*
+                    0x51..=0x60 => Some(OpCode::Num(SmallNum(byte - 0x50))),
+                    0xbb..=0xff => Some(OpCode::Unknown(UnknownOpCode(byte))),
+                    _ => None,
+                }
+            }
+
+            pub fn to_byte(&self) -> Option<u8> {
+                match self {
+                    $(OpCode::$variant => Some($byte),)*
+                    OpCode::Num(n) => Some(0x50 + n.0),
+                    OpCode::Unknown(byte) => Some(byte.0),
+                    OpCode::Push(_) => None,
+                }
+            }
+
+            pub fn from_name(name: &str) -> Option<Self> {
+                match name {
+                    $($name => Some(OpCode::$variant),)*
+                    "OP_FALSE" => Some(OpCode::False),
+                    "OP_TRUE" => Some(OpCode::Num(SmallNum(1))),
+                    "OP_NOP2" => Some(OpCode::CheckLockTimeVerify),
+                    "OP_NOP3" => Some(OpCode::CheckSequenceVerify),
+                    _ => {
+                        OpCode::num(name.strip_prefix("OP_")?.parse().ok()?)
+                    },
+                }
+            }
+
+            /// Name used in the ASM representation, `None` for data-carrying opcodes
+            pub fn name(&self) -> Option<&'static str> {
+                match self {
+                    $(OpCode::$variant => Some($name),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+opcodes! {
+    False = 0x00 => "OP_0",
+    OneNegate = 0x4f => "OP_1NEGATE",
+    Reserved = 0x50 => "OP_RESERVED",
+    Nop = 0x61 => "OP_NOP",
+    Ver = 0x62 => "OP_VER",
+    If = 0x63 => "OP_IF",
+    NotIf = 0x64 => "OP_NOTIF",
+    VerIf = 0x65 => "OP_VERIF",
+    VerNotIf = 0x66 => "OP_VERNOTIF",
+    Else = 0x67 => "OP_ELSE",
+    EndIf = 0x68 => "OP_ENDIF",
+    Verify = 0x69 => "OP_VERIFY",
+    Return = 0x6a => "OP_RETURN",
+    ToAltStack = 0x6b => "OP_TOALTSTACK",
+    FromAltStack = 0x6c => "OP_FROMALTSTACK",
+    TwoDrop = 0x6d => "OP_2DROP",
+    TwoDup = 0x6e => "OP_2DUP",
+    ThreeDup = 0x6f => "OP_3DUP",
+    TwoOver = 0x70 => "OP_2OVER",
+    TwoRot = 0x71 => "OP_2ROT",
+    TwoSwap = 0x72 => "OP_2SWAP",
+    IfDup = 0x73 => "OP_IFDUP",
+    Depth = 0x74 => "OP_DEPTH",
+    Drop = 0x75 => "OP_DROP",
+    Dup = 0x76 => "OP_DUP",
+    Nip = 0x77 => "OP_NIP",
+    Over = 0x78 => "OP_OVER",
+    Pick = 0x79 => "OP_PICK",
+    Roll = 0x7a => "OP_ROLL",
+    Rot = 0x7b => "OP_ROT",
+    Swap = 0x7c => "OP_SWAP",
+    Tuck = 0x7d => "OP_TUCK",
+    Cat = 0x7e => "OP_CAT",
+    Substr = 0x7f => "OP_SUBSTR",
+    Left = 0x80 => "OP_LEFT",
+    Right = 0x81 => "OP_RIGHT",
+    Size = 0x82 => "OP_SIZE",
+    Invert = 0x83 => "OP_INVERT",
+    And = 0x84 => "OP_AND",
+    Or = 0x85 => "OP_OR",
+    Xor = 0x86 => "OP_XOR",
+    Equal = 0x87 => "OP_EQUAL",
+    EqualVerify = 0x88 => "OP_EQUALVERIFY",
+    Reserved1 = 0x89 => "OP_RESERVED1",
+    Reserved2 = 0x8a => "OP_RESERVED2",
+    Add1 = 0x8b => "OP_1ADD",
+    Sub1 = 0x8c => "OP_1SUB",
+    Mul2 = 0x8d => "OP_2MUL",
+    Div2 = 0x8e => "OP_2DIV",
+    Negate = 0x8f => "OP_NEGATE",
+    Abs = 0x90 => "OP_ABS",
+    Not = 0x91 => "OP_NOT",
+    NotEqual0 = 0x92 => "OP_0NOTEQUAL",
+    Add = 0x93 => "OP_ADD",
+    Sub = 0x94 => "OP_SUB",
+    Mul = 0x95 => "OP_MUL",
+    Div = 0x96 => "OP_DIV",
+    Mod = 0x97 => "OP_MOD",
+    LShift = 0x98 => "OP_LSHIFT",
+    RShift = 0x99 => "OP_RSHIFT",
+    BoolAnd = 0x9a => "OP_BOOLAND",
+    BoolOr = 0x9b => "OP_BOOLOR",
+    NumEqual = 0x9c => "OP_NUMEQUAL",
+    NumEqualVerify = 0x9d => "OP_NUMEQUALVERIFY",
+    NumNotEqual = 0x9e => "OP_NUMNOTEQUAL",
+    LessThan = 0x9f => "OP_LESSTHAN",
+    GreaterThan = 0xa0 => "OP_GREATERTHAN",
+    LessThanOrEqual = 0xa1 => "OP_LESSTHANOREQUAL",
+    GreaterThanOrEqual = 0xa2 => "OP_GREATERTHANOREQUAL",
+    Min = 0xa3 => "OP_MIN",
+    Max = 0xa4 => "OP_MAX",
+    Within = 0xa5 => "OP_WITHIN",
+    Ripemd160 = 0xa6 => "OP_RIPEMD160",
+    Sha1 = 0xa7 => "OP_SHA1",
+    Sha256 = 0xa8 => "OP_SHA256",
+    Hash160 = 0xa9 => "OP_HASH160",
+    Hash256 = 0xaa => "OP_HASH256",
+    CodeSeparator = 0xab => "OP_CODESEPARATOR",
+    CheckSig = 0xac => "OP_CHECKSIG",
+    CheckSigVerify = 0xad => "OP_CHECKSIGVERIFY",
+    CheckMultiSig = 0xae => "OP_CHECKMULTISIG",
+    CheckMultiSigVerify = 0xaf => "OP_CHECKMULTISIGVERIFY",
+    Nop1 = 0xb0 => "OP_NOP1",
+    CheckLockTimeVerify = 0xb1 => "OP_CHECKLOCKTIMEVERIFY",
+    CheckSequenceVerify = 0xb2 => "OP_CHECKSEQUENCEVERIFY",
+    Nop4 = 0xb3 => "OP_NOP4",
+    Nop5 = 0xb4 => "OP_NOP5",
+    Nop6 = 0xb5 => "OP_NOP6",
+    Nop7 = 0xb6 => "OP_NOP7",
+    Nop8 = 0xb7 => "OP_NOP8",
+    Nop9 = 0xb8 => "OP_NOP9",
+    Nop10 = 0xb9 => "OP_NOP10",
+    CheckSigAdd = 0xba => "OP_CHECKSIGADD",
+}
+
+impl fmt::Display for OpCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OpCode::Num(n) => write!(f, "OP_{}", n.get()),
+            OpCode::Push(data) => write!(f, "{}", to_hex(data)),
+            OpCode::Unknown(_) => write!(f, "OP_UNKNOWN"),
+            opcode => write!(f, "{}", opcode.name().expect("named opcode")),
+        }
+    }
+}
+
+impl Parse for OpCode {
+    fn parse(bytes: &[u8]) -> Result<(Self, &[u8]), Error> {
+        OpCodeRef::parse(bytes).map(|(opcode, bytes)| (opcode.to_owned(), bytes))
+    }
+}
+
+/// Borrowed counterpart of `OpCode`, pushes point into the parsed buffer
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum OpCodeRef<'a> {
+    Push(&'a [u8]),
+    /// Any other opcode, never an `OpCode::Push`
+    Op(OpCode),
+}
+
+impl<'a> OpCodeRef<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Result<(Self, &'a [u8]), Error> {
+        check_len(bytes, 1)?;
+        #[cfg(feature = "tracing")]
+        tracing::trace!(opcode = bytes[0], remaining = bytes.len(), "parsing opcode");
+
+        match bytes[0] {
+            v @ 1..=75 => {
+                check_len(bytes, v as usize + 1)?;
+                let data = &bytes[1..(v as usize + 1)];
+                Ok((OpCodeRef::Push(data), &bytes[(v as usize + 1)..]))
+            },
+            76 => {
+                check_len(bytes, 2)?;
+                let len = bytes[1] as usize;
+                check_len(bytes, len + 2)?;
+                let data = &bytes[2..(len + 2)];
+                Ok((OpCodeRef::Push(data), &bytes[(len + 2)..]))
+            },
+            77 => {
+                let (len, bytes) = take_bytes(&bytes[1..])?;
+                let len = u16::from_le_bytes(len) as usize;
+                check_len(bytes, len)?;
+                Ok((OpCodeRef::Push(&bytes[..len]), &bytes[len..]))
+            },
+            78 => {
+                let (len, bytes) = take_bytes(&bytes[1..])?;
+                let len = u32::from_le_bytes(len) as usize;
+                check_len(bytes, len)?;
+                Ok((OpCodeRef::Push(&bytes[..len]), &bytes[len..]))
+            },
+
+            op => {
+                let opcode = OpCode::from_byte(op).expect("push opcodes are handled above");
+                Ok((OpCodeRef::Op(opcode), &bytes[1..]))
+            },
+        }
+    }
+
+    pub fn to_owned(&self) -> OpCode {
+        match self {
+            OpCodeRef::Push(data) => OpCode::Push(data.to_vec()),
+            OpCodeRef::Op(opcode) => opcode.clone(),
+        }
+    }
+}
+
+/// A number from 1 to 16, which `OP_1`..`OP_16` push
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(try_from = "u8", into = "u8"))]
+pub struct SmallNum(u8);
+
+impl SmallNum {
+    pub fn new(n: u8) -> Option<Self> {
+        (1..=16).contains(&n).then_some(SmallNum(n))
+    }
+
+    pub fn get(self) -> u8 {
+        self.0
+    }
+}
+
+impl TryFrom<u8> for SmallNum {
+    type Error = Error;
+
+    fn try_from(n: u8) -> Result<Self, Self::Error> {
+        SmallNum::new(n).ok_or(Error::InvalidSmallNum(n))
+    }
+}
+
+impl From<SmallNum> for u8 {
+    fn from(n: SmallNum) -> Self {
+        n.0
+    }
+}
+
+/// A byte from `0xbb` to `0xff`, not assigned to any opcode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(try_from = "u8", into = "u8"))]
+pub struct UnknownOpCode(u8);
+
+impl UnknownOpCode {
+    pub fn new(byte: u8) -> Option<Self> {
+        (0xbb..=0xff).contains(&byte).then_some(UnknownOpCode(byte))
+    }
+
+    pub fn get(self) -> u8 {
+        self.0
+    }
+}
+
+impl TryFrom<u8> for UnknownOpCode {
+    type Error = Error;
+
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        UnknownOpCode::new(byte).ok_or(Error::AssignedOpCode(byte))
+    }
+}
+
+impl From<UnknownOpCode> for u8 {
+    fn from(byte: UnknownOpCode) -> Self {
+        byte.0
+    }
+}
+
+impl OpCode {
+    /// `OP_n`, `None` unless `n` is in `1..=16`
+    pub fn num(n: u8) -> Option<Self> {
+        SmallNum::new(n).map(OpCode::Num)
+    }
+
+    /// Opcodes disabled since 2010, which make a script invalid wherever they appear, even in an
+    /// unexecuted branch
+    pub fn is_disabled(&self) -> bool {
+        use OpCode::*;
+
+        matches!(self, Cat | Substr | Left | Right | Invert | And | Or | Xor | Mul2 | Div2 | Mul | Div | Mod | LShift | RShift)
+    }
+
+    /// Length of the serialized opcode, push prefix included
+    pub fn encoded_len(&self) -> usize {
+        match self {
+            OpCode::Push(data) if !data.is_empty() && data.len() <= 75 => 1 + data.len(),
+            OpCode::Push(data) if data.len() <= 0xFF => 2 + data.len(),
+            OpCode::Push(data) if data.len() <= 0xFFFF => 3 + data.len(),
+            OpCode::Push(data) => 5 + data.len(),
+            _ => 1,
+        }
+    }
+}
+
+impl Encode for OpCode {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            OpCode::Push(data) if !data.is_empty() && data.len() <= 75 => {
+                out.push(data.len() as u8);
+                out.extend_from_slice(data);
+            },
+            // A zero length byte is `OP_0`, so an empty push needs `OP_PUSHDATA1` to parse back
+            OpCode::Push(data) if data.len() <= 0xFF => {
+                out.push(76);
+                out.push(data.len() as u8);
+                out.extend_from_slice(data);
+            },
+            OpCode::Push(data) if data.len() <= 0xFFFF => {
+                out.push(77);
+                out.extend_from_slice(&(data.len() as u16).to_le_bytes());
+                out.extend_from_slice(data);
+            },
+            OpCode::Push(data) => {
+                out.push(78);
+                out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+                out.extend_from_slice(data);
+            },
+            opcode => out.push(opcode.to_byte().expect("push opcodes are handled above")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScriptType {
+    P2pk,
+    P2pkh,
+    P2sh,
+    P2wpkh,
+    P2wsh,
+    P2tr,
+    /// Witness program of a future version, anyone can spend it until a soft fork defines it
+    WitnessUnknown,
+    Multisig,
+    OpReturn,
+    NonStandard,
+}
+
+/// The names reported by Bitcoin Core's `scriptPubKey.type`
+impl fmt::Display for ScriptType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ScriptType::P2pk => "pubkey",
+            ScriptType::P2pkh => "pubkeyhash",
+            ScriptType::P2sh => "scripthash",
+            ScriptType::P2wpkh => "witness_v0_keyhash",
+            ScriptType::P2wsh => "witness_v0_scripthash",
+            ScriptType::P2tr => "witness_v1_taproot",
+            ScriptType::WitnessUnknown => "witness_unknown",
+            ScriptType::Multisig => "multisig",
+            ScriptType::OpReturn => "nulldata",
+            ScriptType::NonStandard => "nonstandard",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Borrowed counterpart of `Script`, parsing it doesn't copy any pushed data
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ScriptRef<'a>(pub Vec<OpCodeRef<'a>>);
+
+impl<'a> ScriptRef<'a> {
+    /// Parses raw script bytes, without the length prefix
+    pub fn from_bytes(mut bytes: &'a [u8]) -> Result<Self, Error> {
+        let mut opcodes = Vec::new();
+        while !bytes.is_empty() {
+            let (opcode, remainder) = OpCodeRef::parse(bytes)?;
+            bytes = remainder;
+            opcodes.push(opcode);
+        }
+
+        Ok(ScriptRef(opcodes))
+    }
+
+    /// Parses a length-prefixed script, like `Script::parse`
+    pub fn parse(bytes: &'a [u8]) -> Result<(Self, &'a [u8]), Error> {
+        let (len, bytes) = VarInt::parse(bytes)?;
+        check_len(bytes, len.0 as usize)?;
+        let (script, bytes) = bytes.split_at(len.0 as usize);
+
+        Ok((ScriptRef::from_bytes(script)?, bytes))
+    }
+
+    /// Pushes are re-encoded with the shortest length prefix
+    pub fn to_owned(&self) -> Script {
+        Script::from_opcodes(&self.0.iter().map(OpCodeRef::to_owned).collect::<Vec<_>>())
+    }
+}
+
+/// Raw script bytes, tokenized on demand. Any script round-trips byte for byte, including
+/// non-minimal pushes and pushes running past the end, which consensus allows in outputs
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Script(pub Vec<u8>);
+
+impl Script {
+    /// Parses raw script bytes, without the length prefix. Fails if they don't tokenize, use
+    /// `Script(bytes.to_vec())` to take them as they are
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        ScriptRef::from_bytes(bytes)?;
+        Ok(Script(bytes.to_vec()))
+    }
+
+    /// Encodes `opcodes`, pushes with the shortest length prefix and an empty one as `OP_0`
+    pub fn from_opcodes(opcodes: &[OpCode]) -> Self {
+        let mut bytes = Vec::new();
+        for opcode in opcodes {
+            match opcode {
+                OpCode::Push(data) if data.is_empty() => OpCode::False.encode(&mut bytes),
+                opcode => opcode.encode(&mut bytes),
+            }
+        }
+        Script(bytes)
+    }
+
+    /// Opcodes with the byte offset where each one starts. Ends after the error of a push
+    /// running past the end of the script
+    pub fn iter(&self) -> impl Iterator<Item = Result<(usize, OpCodeRef<'_>), Error>> + '_ {
+        let mut bytes = self.0.as_slice();
+        core::iter::from_fn(move || {
+            if bytes.is_empty() {
+                return None;
+            }
+
+            let offset = self.0.len() - bytes.len();
+            match OpCodeRef::parse(bytes) {
+                Ok((opcode, remainder)) => {
+                    bytes = remainder;
+                    Some(Ok((offset, opcode)))
+                },
+                Err(err) => {
+                    bytes = &[];
+                    Some(Err(err))
+                },
+            }
+        })
+    }
+
+    /// All the opcodes, failing on a push running past the end of the script
+    pub fn opcodes(&self) -> Result<Vec<OpCode>, Error> {
+        self.iter().map(|opcode| opcode.map(|(_, opcode)| opcode.to_owned())).collect()
+    }
+
+    /// Raw script bytes, without the length prefix
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Raw script bytes, without the length prefix
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.clone()
+    }
+
+    /// Whether every opcode only pushes data, including `OP_0`, `OP_1NEGATE` and `OP_1`..`OP_16`.
+    /// Like Bitcoin Core, `OP_RESERVED` is also accepted since it sorts below `OP_16`
+    pub fn is_push_only(&self) -> bool {
+        use OpCode::*;
+
+        self.iter().all(|opcode| matches!(opcode, Ok((_, OpCodeRef::Push(_) | OpCodeRef::Op(False | OneNegate | Reserved | Num(_))))))
+    }
+
+    /// The data pushed by every push opcode, small numbers included
+    pub fn push_bytes(&self) -> Vec<&[u8]> {
+        const SMALL_NUMS: [u8; 17] = [0x81, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
+
+        self.iter()
+            .map_while(Result::ok)
+            .filter_map(|(_, opcode)| match opcode {
+                OpCodeRef::Push(data) => Some(data),
+                OpCodeRef::Op(OpCode::False) => Some(&[][..]),
+                OpCodeRef::Op(OpCode::OneNegate) => Some(&SMALL_NUMS[..1]),
+                OpCodeRef::Op(OpCode::Num(n)) => Some(core::slice::from_ref(&SMALL_NUMS[n.0 as usize])),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Whether every push uses the smallest possible opcode (BIP62), false if the script
+    /// doesn't tokenize
+    pub fn has_minimal_pushes(&self) -> bool {
+        Script::bytes_have_minimal_pushes(&self.0).unwrap_or(false)
+    }
+
+    /// Like `has_minimal_pushes`, but on raw script bytes and failing if they don't tokenize
+    pub fn bytes_have_minimal_pushes(mut bytes: &[u8]) -> Result<bool, Error> {
+        while !bytes.is_empty() {
+            let op = bytes[0];
+            let (opcode, remainder) = OpCodeRef::parse(bytes)?;
+            bytes = remainder;
+
+            let OpCodeRef::Push(data) = opcode else {
+                continue;
+            };
+            let minimal_op = match data.len() {
+                0..=75 => data.len() as u8,
+                76..=0xFF => 76,
+                0x100..=0xFFFF => 77,
+                _ => 78,
+            };
+            if op != minimal_op || needs_small_opcode(data) {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    pub fn classify(&self) -> ScriptType {
+        use OpCode::*;
+
+        let Ok(opcodes) = self.opcodes() else {
+            return ScriptType::NonStandard;
+        };
+        // Like Core, the fixed templates only match direct pushes, which the total length checks
+        let len = self.0.len();
+        match opcodes.as_slice() {
+            [Push(pk), CheckSig] if is_pubkey(pk) && len == pk.len() + 2 => ScriptType::P2pk,
+            [Dup, Hash160, Push(hash), EqualVerify, CheckSig] if hash.len() == 20 && len == 25 => ScriptType::P2pkh,
+            [Hash160, Push(hash), Equal] if hash.len() == 20 && len == 23 => ScriptType::P2sh,
+            [False, Push(program)] if program.len() == 20 && len == 22 => ScriptType::P2wpkh,
+            [False, Push(program)] if program.len() == 32 && len == 34 => ScriptType::P2wsh,
+            [Num(SmallNum(1)), Push(program)] if program.len() == 32 && len == 34 => ScriptType::P2tr,
+            // Version 0 programs of other lengths can't be spent
+            _ if self.witness_program().is_some_and(|(version, _)| version != 0) => ScriptType::WitnessUnknown,
+            [Return, rest @ ..] if rest.iter().all(|op| matches!(op, Push(_) | False | OneNegate | Reserved | Num(_))) => ScriptType::OpReturn,
+            _ if self.as_multisig().is_some() => ScriptType::Multisig,
+            _ => ScriptType::NonStandard,
+        }
+    }
+
+    /// Matches `OP_m <pubkey>... OP_n OP_CHECKMULTISIG`, returning `m` and the pubkeys
+    pub fn as_multisig(&self) -> Option<(u8, Vec<Vec<u8>>)> {
+        let opcodes = self.opcodes().ok()?;
+        let [OpCode::Num(m), pushes @ .., OpCode::Num(n), OpCode::CheckMultiSig] = opcodes.as_slice() else {
+            return None;
+        };
+        if m > n || pushes.len() != n.0 as usize {
+            return None;
+        }
+
+        let pubkeys = pushes
+            .iter()
+            .map(|op| match op {
+                OpCode::Push(pk) if is_pubkey(pk) => Some(pk.clone()),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some((m.0, pubkeys))
+    }
+
+    /// Disassembles the script the way `bitcoin-cli decodescript` does, including the
+    /// `[error]` for a push running past the end
+    pub fn to_asm(&self) -> String {
+        self.iter()
+            .map(|opcode| match opcode {
+                Ok((_, opcode)) => opcode.to_owned().to_string(),
+                Err(_) => "[error]".to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Inverse of `to_asm`: `OP_*` tokens are opcodes, anything else is hex data to push
+    pub fn from_asm(s: &str) -> Result<Self, Error> {
+        s.split_whitespace()
+            .map(|token| {
+                if token.starts_with("OP_") {
+                    OpCode::from_name(token).ok_or_else(|| Error::UnknownOpCodeName(token.to_string()))
+                } else {
+                    Ok(OpCode::Push(from_hex(token)?))
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(|opcodes| Script::from_opcodes(&opcodes))
+    }
+
+    /// Teaching-oriented interpreter: only a handful of opcodes are supported and
+    /// `OP_CHECKSIG` doesn't actually verify the signature. The lock time opcodes need the
+    /// spending transaction, see `eval_with_context`
+    pub fn eval(&self, stack: &mut Vec<Vec<u8>>) -> Result<bool, Error> {
+        self.run(stack, None)
+    }
+
+    /// Like `eval`, also supporting `OP_CHECKLOCKTIMEVERIFY` and `OP_CHECKSEQUENCEVERIFY`
+    pub fn eval_with_context(&self, stack: &mut Vec<Vec<u8>>, context: &ScriptContext) -> Result<bool, Error> {
+        self.run(stack, Some(context))
+    }
+
+    fn run(&self, stack: &mut Vec<Vec<u8>>, context: Option<&ScriptContext>) -> Result<bool, Error> {
+        fn pop(stack: &mut Vec<Vec<u8>>) -> Result<Vec<u8>, Error> {
+            stack.pop().ok_or(Error::StackUnderflow)
+        }
+
+        for opcode in &self.opcodes()? {
+            match opcode {
+                OpCode::Push(data) => stack.push(data.clone()),
+                OpCode::False => stack.push(vec![]),
+                OpCode::Num(n) => stack.push(vec![n.get()]),
+                OpCode::OneNegate => stack.push(vec![0x81]),
+
+                OpCode::Nop => {},
+                OpCode::Verify => {
+                    if !is_truthy(&pop(stack)?) {
+                        return Ok(false);
+                    }
+                },
+                OpCode::Return => return Ok(false),
+
+                OpCode::Drop => {
+                    pop(stack)?;
+                },
+                OpCode::Dup => {
+                    let top = stack.last().ok_or(Error::StackUnderflow)?.clone();
+                    stack.push(top);
+                },
+
+                OpCode::Equal | OpCode::EqualVerify => {
+                    let equal = pop(stack)? == pop(stack)?;
+                    if matches!(opcode, OpCode::Equal) {
+                        stack.push(if equal { vec![1] } else { vec![] });
+                    } else if !equal {
+                        return Ok(false);
+                    }
+                },
+
+                OpCode::Hash160 => {
+                    let top = pop(stack)?;
+                    stack.push(hash160(&top).to_vec());
+                },
+                OpCode::CheckSig => {
+                    let _pubkey = pop(stack)?;
+                    let _sig = pop(stack)?;
+                    stack.push(vec![1]);
+                },
+
+                OpCode::CheckLockTimeVerify | OpCode::CheckSequenceVerify => {
+                    let byte = opcode.to_byte().expect("not a push");
+                    let context = context.ok_or(Error::UnsupportedOpCode(byte))?;
+                    // The argument is left on the stack, these used to be `OP_NOP`s
+                    let top = stack.last().ok_or(Error::StackUnderflow)?;
+                    let locktime = script_num::decode_with_max_size(top, script_num::MAX_LOCKTIME_NUM_SIZE)?;
+                    let satisfied = match opcode {
+                        OpCode::CheckLockTimeVerify => context.check_lock_time(locktime),
+                        _ => context.check_sequence(locktime),
+                    };
+                    if !satisfied {
+                        return Ok(false);
+                    }
+                },
+
+                opcode => return Err(Error::UnsupportedOpCode(opcode.to_byte().expect("pushes are supported"))),
+            }
+        }
+
+        Ok(stack.last().is_some_and(|top| is_truthy(top)))
+    }
+
+    /// BIP141 witness version and program: a version opcode followed by a direct push of 2 to
+    /// 40 bytes
+    pub fn witness_program(&self) -> Option<(u8, &[u8])> {
+        match self.0.as_slice() {
+            [version @ (0x00 | 0x51..=0x60), len, program @ ..] if (2..=40).contains(len) && *len as usize == program.len() => {
+                Some((version.saturating_sub(0x50), program))
+            },
+            _ => None,
+        }
+    }
+
+    /// Serialized size, without the length prefix
+    pub fn size(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Provably unspendable outputs, which Core never adds to the UTXO set: scripts starting
+    /// with `OP_RETURN` or longer than `MAX_SCRIPT_SIZE`
+    pub fn is_unspendable(&self) -> bool {
+        self.0.first() == Some(&0x6a) || self.size() > MAX_SCRIPT_SIZE
+    }
+
+    /// Whether any opcode is disabled, see `OpCode::is_disabled`
+    pub fn contains_disabled_opcode(&self) -> bool {
+        self.iter().map_while(Result::ok).any(|(_, opcode)| matches!(opcode, OpCodeRef::Op(opcode) if opcode.is_disabled()))
+    }
+
+    /// Policy rules for a P2WSH witness script: at most `MAX_STANDARD_P2WSH_SCRIPT_SIZE` bytes
+    /// and no disabled opcode, which would fail the spend even in an unexecuted branch
+    pub fn is_valid_witness_script(&self) -> bool {
+        self.size() <= MAX_STANDARD_P2WSH_SCRIPT_SIZE && self.is_consensus_valid_witness_script()
+    }
+
+    /// Consensus rules for a P2WSH witness script: at most `MAX_SCRIPT_SIZE` bytes and no
+    /// disabled opcode. Larger scripts are valid in tapscript, which has no size limit
+    pub fn is_consensus_valid_witness_script(&self) -> bool {
+        self.size() <= MAX_SCRIPT_SIZE && !self.contains_disabled_opcode()
+    }
+
+    /// The `MINIMALIF` rule of segwit spends: the argument of `OP_IF`/`OP_NOTIF` must be
+    /// either empty or exactly `0x01`
+    pub fn minimal_if(arg: &[u8]) -> bool {
+        matches!(arg, [] | [0x01])
+    }
+
+    /// Data carried by an `OP_RETURN <push>` output
+    pub fn op_return_data(&self) -> Option<Vec<u8>> {
+        match self.opcodes().ok()?.as_slice() {
+            [OpCode::Return, OpCode::Push(data)] => Some(data.clone()),
+            _ => None,
+        }
+    }
+
+    /// Like `op_return_data`, but accepts any number of pushes and concatenates them
+    pub fn op_return_data_concatenated(&self) -> Option<Vec<u8>> {
+        match self.opcodes().ok()?.as_slice() {
+            [OpCode::Return, pushes @ ..] if !pushes.is_empty() => pushes
+                .iter()
+                .map(|op| match op {
+                    OpCode::Push(data) => Some(data.as_slice()),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()
+                .map(|data| data.concat()),
+            _ => None,
+        }
+    }
+
+    pub fn address(&self, network: Network) -> Option<String> {
+        let base58_address = |prefix: u8, hash: &[u8]| {
+            let mut payload = vec![prefix];
+            payload.extend_from_slice(hash);
+            base58::encode_check(&payload)
+        };
+
+        let opcodes = self.opcodes().ok()?;
+        match (self.classify(), opcodes.as_slice()) {
+            (ScriptType::P2pkh, [_, _, OpCode::Push(hash), ..]) => Some(base58_address(network.p2pkh_prefix(), hash)),
+            (ScriptType::P2sh, [_, OpCode::Push(hash), _]) => Some(base58_address(network.p2sh_prefix(), hash)),
+            (ScriptType::P2wpkh | ScriptType::P2wsh, [_, OpCode::Push(program)]) => {
+                Some(bech32::encode(network.bech32_hrp(), 0, program))
+            },
+            (ScriptType::P2tr, [_, OpCode::Push(program)]) => Some(bech32::encode(network.bech32_hrp(), 1, program)),
+            (ScriptType::WitnessUnknown, _) => {
+                self.witness_program().map(|(version, program)| bech32::encode(network.bech32_hrp(), version, program))
+            },
+            _ => None,
+        }
+    }
+}
+
+/// The spending transaction, as seen by the lock time opcodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ScriptContext {
+    /// `OP_CHECKSEQUENCEVERIFY` fails for version 1 transactions
+    pub tx_version: u32,
+    pub tx_locktime: u32,
+    pub input_sequence: u32,
+    pub input_index: usize,
+}
+
+impl ScriptContext {
+    /// Context of the script of `tx.inputs[input_index]`. Panics if the index is out of range
+    pub fn new(tx: &Transaction, input_index: usize) -> Self {
+        ScriptContext {
+            tx_version: tx.version,
+            tx_locktime: tx.locktime,
+            input_sequence: tx.inputs[input_index].sequence,
+            input_index,
+        }
+    }
+
+    /// BIP65: the transaction lock time must be of the same kind and at least `locktime`, and
+    /// actually enforced by a non-final sequence
+    fn check_lock_time(&self, locktime: i64) -> bool {
+        let threshold = Transaction::LOCKTIME_THRESHOLD as i64;
+        let tx_locktime = self.tx_locktime as i64;
+        locktime >= 0
+            && (locktime < threshold) == (tx_locktime < threshold)
+            && locktime <= tx_locktime
+            && self.input_sequence != 0xFFFFFFFF
+    }
+
+    /// BIP112: the input's relative lock time must be of the same kind and at least `sequence`.
+    /// An argument with the disable flag makes the opcode a no-op
+    fn check_sequence(&self, sequence: i64) -> bool {
+        if sequence < 0 {
+            return false;
+        }
+        if sequence & TxIn::SEQUENCE_LOCKTIME_DISABLE_FLAG as i64 != 0 {
+            return true;
+        }
+        if self.tx_version < 2 || self.input_sequence & TxIn::SEQUENCE_LOCKTIME_DISABLE_FLAG != 0 {
+            return false;
+        }
+
+        let type_flag = TxIn::SEQUENCE_LOCKTIME_TYPE_FLAG as i64;
+        let input_sequence = self.input_sequence as i64;
+        sequence & type_flag == input_sequence & type_flag && sequence & 0xffff <= input_sequence & 0xffff
+    }
+}
+
+/// Consensus limit on the size of a single push
+pub const MAX_PUSH_SIZE: usize = 520;
+/// Consensus limit on the size of an executed script
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+/// Largest witness script relayed by Core for P2WSH spends
+pub const MAX_STANDARD_P2WSH_SCRIPT_SIZE: usize = 3600;
+
+/// Builds a `Script` one opcode at a time, always choosing the minimal push encoding
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScriptBuilder(Vec<OpCode>);
+
+impl ScriptBuilder {
+    pub fn new() -> Self {
+        ScriptBuilder::default()
+    }
+
+    pub fn push_opcode(mut self, opcode: OpCode) -> Self {
+        self.0.push(opcode);
+        self
+    }
+
+    /// Pushes `data` with `OP_0`, `OP_1NEGATE` or `OP_1`..`OP_16` where possible. Errors on
+    /// pushes larger than `MAX_PUSH_SIZE`
+    pub fn push_slice(self, data: &[u8]) -> Result<Self, Error> {
+        if data.len() > MAX_PUSH_SIZE {
+            return Err(Error::PushTooLarge(data.len()));
+        }
+
+        let opcode = match data {
+            [] => OpCode::False,
+            [0x81] => OpCode::OneNegate,
+            [n @ 1..=16] => OpCode::Num(SmallNum(*n)),
+            _ => OpCode::Push(data.to_vec()),
+        };
+        Ok(self.push_opcode(opcode))
+    }
+
+    /// Pushes `n` as a minimally-encoded script number
+    pub fn push_int(self, n: i64) -> Self {
+        self.push_slice(&script_num::encode(n)).expect("script numbers are at most 9 bytes")
+    }
+
+    pub fn into_script(self) -> Script {
+        Script::from_opcodes(&self.0)
+    }
+}
+
+/// Pushes that have a dedicated opcode: the empty vector, `-1` and `1`..`16`
+fn needs_small_opcode(data: &[u8]) -> bool {
+    matches!(data, [] | [0x81] | [1..=16])
+}
+
+/// Compressed or uncompressed public key length
+fn is_pubkey(data: &[u8]) -> bool {
+    data.len() == 33 || data.len() == 65
+}
+
+/// Any non-zero value is true, except for "negative zero"
+fn is_truthy(data: &[u8]) -> bool {
+    match data.split_last() {
+        Some((last, rest)) => rest.iter().any(|b| *b != 0) || (*last != 0 && *last != 0x80),
+        None => false,
+    }
+}
+
+/// Same as `Script::parse_exact`, the length prefix included. Use `Script::from_bytes` for the
+/// raw script bytes
+impl TryFrom<&[u8]> for Script {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Script::parse_exact(bytes)
+    }
+}
+
+impl Parse for Script {
+    fn parse(bytes: &[u8]) -> Result<(Self, &[u8]), Error> {
+        Script::parse_with_max_len(bytes, usize::MAX, ParseMode::Lenient)
+    }
+}
+
+impl Script {
+    /// Like `parse`, failing with `Error::LimitExceeded` before copying more than `max_len` bytes
+    pub(crate) fn parse_with_max_len(bytes: &[u8], max_len: usize, mode: ParseMode) -> Result<(Self, &[u8]), Error> {
+        parse_bytes_with_limit(bytes, max_len, mode).map(|(script, bytes)| (Script(script), bytes))
+    }
+}
+
+#[cfg(feature = "std")]
+impl Decode for Script {
+    fn decode<R: Read>(r: &mut R) -> Result<Self, Error> {
+        Ok(Script(read_var_bytes(r)?))
+    }
+}
+
+/// Serialized as `{"asm": ..., "hex": ...}`, only the hex is read back
+#[cfg(feature = "serde")]
+impl serde::Serialize for Script {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut script = s.serialize_struct("Script", 2)?;
+        script.serialize_field("asm", &self.to_asm())?;
+        script.serialize_field("hex", &to_hex(&self.0))?;
+        script.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Script {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        struct ScriptHex {
+            #[serde(with = "crate::serde_utils::hex_bytes")]
+            hex: Vec<u8>,
+        }
+
+        let ScriptHex { hex } = ScriptHex::deserialize(d)?;
+        Ok(Script(hex))
+    }
+}
+
+impl Encode for Script {
+    fn encode(&self, out: &mut Vec<u8>) {
+        VarInt(self.0.len() as u64).encode(out);
+        out.extend_from_slice(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pushdata() {
+        let mut script = vec![0x4d, 0x00, 0x01];
+        script.extend_from_slice(&[0xab; 256]);
+        let (opcode, bytes) = OpCode::parse(&script).unwrap();
+        assert!(bytes.is_empty());
+        assert!(matches!(&opcode, OpCode::Push(data) if data == &[0xab; 256]));
+
+        let mut encoded = Vec::new();
+        opcode.encode(&mut encoded);
+        assert_eq!(encoded, script);
+
+        let (opcode, bytes) = OpCode::parse(&[0x4e, 0x02, 0x00, 0x00, 0x00, 0xaa, 0xbb, 0xcc]).unwrap();
+        assert_eq!(bytes, &[0xcc]);
+        assert!(matches!(opcode, OpCode::Push(data) if data == [0xaa, 0xbb]));
+
+        assert_eq!(OpCode::parse(&[0x4d, 0x01]).err(), Some(Error::InsufficientBytes { needed: 2, got: 1 }));
+        assert_eq!(OpCode::parse(&[0x4e, 0xff, 0xff, 0xff, 0xff, 0x00]).err(), Some(Error::InsufficientBytes { needed: 0xffffffff, got: 1 }));
+    }
+
+    fn script(hex: &str) -> Script {
+        Script::from_bytes(&from_hex(hex).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(script("76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac").classify(), ScriptType::P2pkh);
+        assert_eq!(script("a914b472a266d0bd89c13706a4132ccfb16f7c3b9fcb87").classify(), ScriptType::P2sh);
+        assert_eq!(script("0014751e76e8199196d454941c45d1b3a323f1433bd6").classify(), ScriptType::P2wpkh);
+        assert_eq!(script("00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262").classify(), ScriptType::P2wsh);
+        assert_eq!(script("5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c").classify(), ScriptType::P2tr);
+        assert_eq!(script("6a0b68656c6c6f20776f726c64").classify(), ScriptType::OpReturn);
+        assert_eq!(script("6a").classify(), ScriptType::OpReturn);
+        assert_eq!(script("6a0051").classify(), ScriptType::OpReturn);
+        assert_eq!(script("6a76").classify(), ScriptType::NonStandard);
+        assert_eq!(script("6a0401020304ac").classify(), ScriptType::NonStandard);
+        assert_eq!(
+            script("4104ae1a62fe09c5f51b13905f07f06b99a2f7159b2225f374cd378d71302fa28414e7aab37397f554a7df5f142c21c1b7303b8a0626f1baded5c72a704f7e6cd84cac").classify(),
+            ScriptType::P2pk
+        );
+        assert_eq!(
+            script("5121022afc20bf379bc96a2f4e9e63ffceb8652b2b6a097f63fbee6ecec2a49a48010e2103a767c7221e9f15f870f1ad9311f5ab937d79fcaeee15bb2c722bca515581b4c052ae").classify(),
+            ScriptType::Multisig
+        );
+        // 2-of-1 is not a valid multisig
+        assert_eq!(
+            script("5221022afc20bf379bc96a2f4e9e63ffceb8652b2b6a097f63fbee6ecec2a49a48010e51ae").classify(),
+            ScriptType::NonStandard
+        );
+        assert_eq!(script("0013751e76e8199196d454941c45d1b3a323f1433b").classify(), ScriptType::NonStandard);
+        assert_eq!(ScriptType::P2wpkh.to_string(), "witness_v0_keyhash");
+
+        // Taproot only has 32 bytes programs, versions 2 to 16 aren't defined yet
+        assert_eq!(script("5110751e76e8199196d454941c45d1b3a323").classify(), ScriptType::WitnessUnknown);
+        assert_eq!(script("5228751e76e8199196d454941c45d1b3a323f1433bd6751e76e8199196d454941c45d1b3a323f1433bd6").classify(), ScriptType::WitnessUnknown);
+        assert_eq!(script("6002751e").classify(), ScriptType::WitnessUnknown);
+        assert_eq!(script("6001aa").classify(), ScriptType::NonStandard);
+        assert_eq!(script("6029751e76e8199196d454941c45d1b3a323f1433bd6751e76e8199196d454941c45d1b3a323f1433bd6aa").classify(), ScriptType::NonStandard);
+        assert_eq!(ScriptType::WitnessUnknown.to_string(), "witness_unknown");
+    }
+
+    #[test]
+    fn test_address() {
+        let address = |hex, network| script(hex).address(network);
+
+        assert_eq!(
+            address("76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac", Network::Mainnet).as_deref(),
+            Some("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa")
+        );
+        assert_eq!(
+            address("a914b472a266d0bd89c13706a4132ccfb16f7c3b9fcb87", Network::Mainnet).as_deref(),
+            Some("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy")
+        );
+        assert_eq!(
+            address("0014751e76e8199196d454941c45d1b3a323f1433bd6", Network::Mainnet).as_deref(),
+            Some("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4")
+        );
+        assert_eq!(
+            address("0014751e76e8199196d454941c45d1b3a323f1433bd6", Network::Regtest).as_deref(),
+            Some("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080")
+        );
+        assert_eq!(
+            address("00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262", Network::Testnet).as_deref(),
+            Some("tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7")
+        );
+        assert_eq!(
+            address("5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c", Network::Mainnet).as_deref(),
+            Some("bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr")
+        );
+        // BIP350 test vectors
+        assert_eq!(address("6002751e", Network::Mainnet).as_deref(), Some("bc1sw50qgdz25j"));
+        assert_eq!(
+            address("5210751e76e8199196d454941c45d1b3a323", Network::Mainnet).as_deref(),
+            Some("bc1zw508d6qejxtdg4y5r3zarvaryvaxxpcs")
+        );
+        assert_eq!(address("6a0b68656c6c6f20776f726c64", Network::Mainnet), None);
+    }
+
+    #[test]
+    fn test_as_multisig() {
+        let pk1 = "022afc20bf379bc96a2f4e9e63ffceb8652b2b6a097f63fbee6ecec2a49a48010e";
+        let pk2 = "03a767c7221e9f15f870f1ad9311f5ab937d79fcaeee15bb2c722bca515581b4c0";
+        let multisig = |asm: String| Script::from_asm(&asm).unwrap().as_multisig();
+
+        assert_eq!(
+            multisig(format!("OP_2 {} {} OP_2 OP_CHECKMULTISIG", pk1, pk2)),
+            Some((2, vec![from_hex(pk1).unwrap(), from_hex(pk2).unwrap()]))
+        );
+        assert_eq!(multisig(format!("OP_1 {} OP_1 OP_CHECKMULTISIG", pk1)), Some((1, vec![from_hex(pk1).unwrap()])));
+        assert_eq!(multisig(format!("OP_3 {} {} OP_2 OP_CHECKMULTISIG", pk1, pk2)), None);
+        assert_eq!(multisig(format!("OP_1 {} {} OP_3 OP_CHECKMULTISIG", pk1, pk2)), None);
+        assert_eq!(multisig(format!("OP_1 {} OP_DUP OP_2 OP_CHECKMULTISIG", pk1)), None);
+        assert_eq!(multisig(format!("OP_1 {} OP_1 OP_CHECKSIG", pk1)), None);
+    }
+
+    #[test]
+    fn test_to_asm() {
+        assert_eq!(
+            script("76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac").to_asm(),
+            "OP_DUP OP_HASH160 62e907b15cbf27d5425399ebf6f0fb50ebb88f18 OP_EQUALVERIFY OP_CHECKSIG"
+        );
+        assert_eq!(
+            script("5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c").to_asm(),
+            "OP_1 a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c"
+        );
+        assert_eq!(script("6a0b68656c6c6f20776f726c64").to_asm(), "OP_RETURN 68656c6c6f20776f726c64");
+        assert_eq!(script("00604f6d9392ff").to_asm(), "OP_0 OP_16 OP_1NEGATE OP_2DROP OP_ADD OP_0NOTEQUAL OP_UNKNOWN");
+        assert_eq!(Script(vec![]).to_asm(), "");
+    }
+
+    #[test]
+    fn test_from_asm() {
+        for hex in [
+            "76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac",
+            "5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c",
+            "00604f6d9392",
+        ] {
+            let script = script(hex);
+            assert_eq!(Script::from_asm(&script.to_asm()), Ok(script));
+        }
+
+        let mut encoded = Vec::new();
+        Script::from_asm(&format!("OP_RETURN {}", "ab".repeat(80))).unwrap().encode(&mut encoded);
+        assert_eq!(&encoded[..4], &[83, 0x6a, 0x4c, 80]);
+
+        assert_eq!(Script::from_asm("OP_TRUE OP_NOP2"), Ok(Script::from_opcodes(&[OpCode::num(1).unwrap(), OpCode::CheckLockTimeVerify])));
+        assert_eq!(Script::from_asm("OP_17"), Err(Error::UnknownOpCodeName("OP_17".into())));
+        assert_eq!(Script::from_asm("OP_DUP OP_FOO"), Err(Error::UnknownOpCodeName("OP_FOO".into())));
+        assert_eq!(Script::from_asm("abc"), Err(Error::OddHexLength));
+    }
+
+    #[test]
+    fn test_script_ref() {
+        let bytes = from_hex("1976a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888acff").unwrap();
+        let (script_ref, remainder) = ScriptRef::parse(&bytes).unwrap();
+        assert_eq!(remainder, &[0xff]);
+        assert_eq!(script_ref.0[1], OpCodeRef::Op(OpCode::Hash160));
+        match script_ref.0[2] {
+            OpCodeRef::Push(data) => assert_eq!(data.as_ptr(), bytes[4..].as_ptr()),
+            _ => panic!("expected a push"),
+        }
+        assert_eq!(script_ref.to_owned(), Script::parse(&bytes).unwrap().0);
+
+        let block_bytes = from_hex(include_str!("../block.hex").trim()).unwrap();
+        let block = crate::Block::parse_exact(&block_bytes).unwrap();
+        for txout in block.transactions.iter().flat_map(|tx| &tx.outputs) {
+            let raw = txout.script_pubkey.to_bytes();
+            assert_eq!(ScriptRef::from_bytes(&raw).unwrap().to_owned(), txout.script_pubkey);
+        }
+
+        assert_eq!(ScriptRef::from_bytes(&[0x4c]), Err(Error::InsufficientBytes { needed: 2, got: 1 }));
+    }
+
+    #[test]
+    fn test_push_only() {
+        let script_sig = script("00473044022064f12ec6aa9d09348ae2a8afb10e1062d4b70e4d6673f5966345330ebe8d4592022048fa3d593074046c16484476236f54c7e0df030f73f706a70c36dd95ee780e82014f5160");
+        assert!(script_sig.is_push_only());
+        let pushes = script_sig.push_bytes();
+        assert_eq!(pushes.len(), 5);
+        assert_eq!(pushes[0], &[] as &[u8]);
+        assert_eq!(pushes[1].len(), 71);
+        assert_eq!(pushes[2..], [&[0x81][..], &[1], &[16]]);
+
+        let p2pkh = script("76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac");
+        assert!(!p2pkh.is_push_only());
+        assert_eq!(p2pkh.push_bytes(), vec![&from_hex("62e907b15cbf27d5425399ebf6f0fb50ebb88f18").unwrap()[..]]);
+        assert!(Script(vec![]).is_push_only());
+    }
+
+    #[test]
+    fn test_minimal_pushes() {
+        assert!(script("76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac").has_minimal_pushes());
+        assert!(Script::from_opcodes(&[OpCode::num(5).unwrap(), OpCode::Push(vec![17])]).has_minimal_pushes());
+        assert!(!Script::from_opcodes(&[OpCode::Push(vec![5])]).has_minimal_pushes());
+        assert!(!Script::from_opcodes(&[OpCode::Push(vec![0x81])]).has_minimal_pushes());
+        assert!(!Script(vec![0x4c, 0x00]).has_minimal_pushes());
+        assert!(!Script(vec![0x4d, 0x01, 0x00, 0xaa]).has_minimal_pushes());
+
+        let minimal = |hex: &str| Script::bytes_have_minimal_pushes(&from_hex(hex).unwrap());
+        assert_eq!(minimal("0001115560"), Ok(true));
+        assert_eq!(minimal(&format!("4c4c{}", "aa".repeat(76))), Ok(true));
+        assert_eq!(minimal("0105"), Ok(false));
+        assert_eq!(minimal("4c01aa"), Ok(false));
+        assert_eq!(minimal("4d0100aa"), Ok(false));
+        assert_eq!(minimal("4c00"), Ok(false));
+        assert_eq!(minimal("4c05aa"), Err(Error::InsufficientBytes { needed: 7, got: 3 }));
+    }
+
+    #[test]
+    fn test_push_length_overrun() {
+        // Pushes claiming more bytes than the length-prefixed script holds, with data following it.
+        // The script is still valid, only tokenizing it fails
+        let parse = |hex: &str| Script::parse(&from_hex(hex).unwrap()).unwrap().0.opcodes();
+        assert_eq!(parse("0205aaffffffffff"), Err(Error::InsufficientBytes { needed: 6, got: 2 }));
+        assert_eq!(parse("034c05aaffffffffff"), Err(Error::InsufficientBytes { needed: 7, got: 3 }));
+        assert_eq!(parse("044d0500aaffffffffff"), Err(Error::InsufficientBytes { needed: 5, got: 1 }));
+        assert_eq!(parse("064e05000000aaffffffffff"), Err(Error::InsufficientBytes { needed: 5, got: 1 }));
+        assert_eq!(parse("024dffffffffff"), Err(Error::InsufficientBytes { needed: 2, got: 1 }));
+        assert_eq!(parse("014c"), Err(Error::InsufficientBytes { needed: 2, got: 1 }));
+    }
+
+    #[test]
+    fn test_try_from() {
+        let bytes = from_hex("1976a914000000000000000000000000000000000000000088ac").unwrap();
+        assert_eq!(Script::try_from(&bytes[..]), Script::from_bytes(&bytes[1..]));
+        assert_eq!(Script::try_from(&bytes[..bytes.len() - 1]), Err(Error::InsufficientBytes { needed: 25, got: 24 }));
+        let mut trailing = bytes.clone();
+        trailing.push(0x00);
+        assert_eq!(Script::try_from(&trailing[..]), Err(Error::TrailingBytes(1)));
+    }
+
+    #[test]
+    fn test_raw_bytes_roundtrip() {
+        // `OP_PUSHDATA1 0x01 0x07` could have been a direct push
+        let bytes = from_hex("4c0107").unwrap();
+        let pushdata1 = Script::from_bytes(&bytes).unwrap();
+        assert_eq!(pushdata1.opcodes(), Ok(vec![OpCode::Push(vec![0x07])]));
+        assert_eq!(pushdata1.to_bytes(), bytes);
+        assert_eq!(pushdata1.size(), 3);
+        assert!(!pushdata1.has_minimal_pushes());
+        let mut encoded = Vec::new();
+        pushdata1.encode(&mut encoded);
+        assert_eq!(Script::parse_exact(&encoded), Ok(pushdata1));
+
+        // Only executing it would fail
+        let truncated = Script::parse_exact(&from_hex("0376a94c").unwrap()).unwrap();
+        assert_eq!(truncated.opcodes(), Err(Error::InsufficientBytes { needed: 2, got: 1 }));
+        assert_eq!(truncated.to_asm(), "OP_DUP OP_HASH160 [error]");
+        assert_eq!(truncated.classify(), ScriptType::NonStandard);
+        assert_eq!(Script::from_bytes(truncated.as_bytes()), Err(Error::InsufficientBytes { needed: 2, got: 1 }));
+
+        // Core only matches the templates with direct pushes
+        let p2sh = script("a914000000000000000000000000000000000000000087");
+        assert_eq!(p2sh.classify(), ScriptType::P2sh);
+        let non_minimal_p2sh = script("a94c14000000000000000000000000000000000000000087");
+        assert_eq!(non_minimal_p2sh.opcodes(), p2sh.opcodes());
+        assert_eq!(non_minimal_p2sh.classify(), ScriptType::NonStandard);
+        assert_eq!(script("004c140000000000000000000000000000000000000000").witness_program(), None);
+    }
+
+    #[test]
+    fn test_iter_offsets() {
+        let p2pkh = script("76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac");
+        let offsets = p2pkh.iter().map(|opcode| opcode.unwrap().0).collect::<Vec<_>>();
+        assert_eq!(offsets, vec![0, 1, 2, 23, 24]);
+        assert_eq!(p2pkh.iter().nth(3), Some(Ok((23, OpCodeRef::Op(OpCode::EqualVerify)))));
+
+        let bytes = from_hex(&format!("4c50{}4d0001{}4e0000010000", "aa".repeat(80), "bb".repeat(256))).unwrap();
+        let script = Script::from_bytes(&bytes[..bytes.len() - 6]).unwrap();
+        assert_eq!(script.iter().map(|opcode| opcode.unwrap().0).collect::<Vec<_>>(), vec![0, 82]);
+        for (offset, opcode) in script.iter().map(Result::unwrap) {
+            let opcode = opcode.to_owned();
+            let mut encoded = Vec::new();
+            opcode.encode(&mut encoded);
+            assert_eq!(encoded.len(), opcode.encoded_len());
+            assert_eq!(bytes[offset..offset + encoded.len()], encoded[..]);
+        }
+        assert_eq!(OpCode::Push(vec![0; 0x10000]).encoded_len(), 5 + 0x10000);
+
+        // Offsets are into the original bytes, a non-minimal push takes the bytes it used
+        let non_minimal = Script::from_bytes(&from_hex(&format!("76a94d1400{}88ac", "00".repeat(20))).unwrap()).unwrap();
+        let offsets = non_minimal.iter().map(|opcode| opcode.unwrap().0).collect::<Vec<_>>();
+        assert_eq!(offsets, vec![0, 1, 2, 25, 26]);
+        assert_eq!(non_minimal.as_bytes()[25], OpCode::EqualVerify.to_byte().unwrap());
+        let truncated = Script(from_hex("764c05aa").unwrap());
+        assert_eq!(truncated.iter().collect::<Vec<_>>(), vec![Ok((0, OpCodeRef::Op(OpCode::Dup))), Err(Error::InsufficientBytes { needed: 7, got: 3 })]);
+    }
+
+    #[test]
+    fn test_script_builder() {
+        let pubkey_hash = from_hex("62e907b15cbf27d5425399ebf6f0fb50ebb88f18").unwrap();
+        let p2pkh = ScriptBuilder::new()
+            .push_opcode(OpCode::Dup)
+            .push_opcode(OpCode::Hash160)
+            .push_slice(&pubkey_hash)
+            .unwrap()
+            .push_opcode(OpCode::EqualVerify)
+            .push_opcode(OpCode::CheckSig)
+            .into_script();
+        assert_eq!(p2pkh, script("76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac"));
+
+        let built = ScriptBuilder::new()
+            .push_int(0)
+            .push_int(-1)
+            .push_int(16)
+            .push_int(17)
+            .push_int(-1000)
+            .push_slice(&[0x05])
+            .unwrap()
+            .push_slice(&[0xaa; 76])
+            .unwrap()
+            .push_slice(&[0xbb; MAX_PUSH_SIZE])
+            .unwrap()
+            .push_opcode(OpCode::CheckLockTimeVerify)
+            .into_script();
+        assert_eq!(built.to_asm().split(' ').take(6).collect::<Vec<_>>(), ["OP_0", "OP_1NEGATE", "OP_16", "11", "e883", "OP_5"]);
+        assert_eq!(Script::from_bytes(&built.to_bytes()), Ok(built.clone()));
+        assert_eq!(Script::bytes_have_minimal_pushes(&built.to_bytes()), Ok(true));
+
+        assert_eq!(ScriptBuilder::new().push_slice(&[0; MAX_PUSH_SIZE + 1]), Err(Error::PushTooLarge(MAX_PUSH_SIZE + 1)));
+    }
+
+    #[test]
+    fn test_eval() {
+        let pubkey = from_hex("0411db93e1dcdb8a016b49840f8c53bc1eb68a382e97b1482ecad7b148a6909a5cb2e0eaddfb84ccf9744464f82e160bfa9b8b64f9d4c03f999b8643f656b412a3").unwrap();
+        let script_pubkey = Script::from_asm("OP_DUP OP_HASH160 11b366edfc0a8b66feebae5c2e25a7b6a5d1cf31 OP_EQUALVERIFY OP_CHECKSIG").unwrap();
+        assert_eq!(script_pubkey.address(Network::Mainnet).as_deref(), Some("12cbQLTFMXRnSzktFkuoG3eHoMeFtpTu3S"));
+
+        let mut stack = vec![vec![0x30; 71], pubkey.clone()];
+        assert_eq!(script_pubkey.eval(&mut stack), Ok(true));
+
+        let mut wrong_pubkey = pubkey;
+        wrong_pubkey[1] ^= 0xff;
+        let mut stack = vec![vec![0x30; 71], wrong_pubkey];
+        assert_eq!(script_pubkey.eval(&mut stack), Ok(false));
+
+        assert_eq!(script_pubkey.eval(&mut vec![]), Err(Error::StackUnderflow));
+        assert_eq!(Script::from_asm("OP_1 OP_2 OP_EQUAL").unwrap().eval(&mut vec![]), Ok(false));
+        assert_eq!(Script::from_asm("OP_2 OP_2 OP_EQUAL").unwrap().eval(&mut vec![]), Ok(true));
+        assert_eq!(Script::from_asm("OP_1 OP_RETURN").unwrap().eval(&mut vec![]), Ok(false));
+        assert_eq!(Script::from_asm("OP_1 OP_ADD").unwrap().eval(&mut vec![]), Err(Error::UnsupportedOpCode(0x93)));
+        assert_eq!(Script::from_asm("80").unwrap().eval(&mut vec![]), Ok(false));
+    }
+
+    #[test]
+    fn test_eval_locktime() {
+        let context = ScriptContext { tx_version: 2, tx_locktime: 500, input_sequence: 0xFFFFFFFE, input_index: 0 };
+        let cltv = |locktime: i64| {
+            let script = ScriptBuilder::new().push_int(locktime).push_opcode(OpCode::CheckLockTimeVerify).into_script();
+            script.eval_with_context(&mut vec![], &context)
+        };
+        assert_eq!(cltv(499), Ok(true));
+        assert_eq!(cltv(500), Ok(true));
+        assert_eq!(cltv(501), Ok(false));
+        assert_eq!(cltv(-1), Ok(false));
+        // A timestamp can't satisfy a height lock
+        assert_eq!(cltv(500_000_001), Ok(false));
+        assert_eq!(cltv(1 << 40), Err(Error::ScriptNumTooLarge { len: 6, max: 5 }));
+        assert_eq!(Script::from_opcodes(&[OpCode::CheckLockTimeVerify]).eval_with_context(&mut vec![], &context), Err(Error::StackUnderflow));
+        assert_eq!(Script::from_asm("OP_1 OP_NOP2").unwrap().eval(&mut vec![]), Err(Error::UnsupportedOpCode(0xb1)));
+        let final_sequence = ScriptContext { input_sequence: 0xFFFFFFFF, ..context };
+        assert_eq!(Script::from_asm("OP_1 OP_NOP2").unwrap().eval_with_context(&mut vec![], &final_sequence), Ok(false));
+
+        let csv = |sequence: i64, context: &ScriptContext| {
+            let script = ScriptBuilder::new().push_int(sequence).push_opcode(OpCode::CheckSequenceVerify).into_script();
+            script.eval_with_context(&mut vec![], context)
+        };
+        let context = ScriptContext { input_sequence: 144, ..context };
+        assert_eq!(csv(144, &context), Ok(true));
+        assert_eq!(csv(145, &context), Ok(false));
+        assert_eq!(csv(TxIn::SEQUENCE_LOCKTIME_TYPE_FLAG as i64 | 1, &context), Ok(false));
+        assert_eq!(csv(TxIn::SEQUENCE_LOCKTIME_DISABLE_FLAG as i64, &context), Ok(true));
+        assert_eq!(csv(144, &ScriptContext { tx_version: 1, ..context }), Ok(false));
+        let time_lock = ScriptContext { input_sequence: TxIn::SEQUENCE_LOCKTIME_TYPE_FLAG | 10, ..context };
+        assert_eq!(csv(TxIn::SEQUENCE_LOCKTIME_TYPE_FLAG as i64 | 10, &time_lock), Ok(true));
+        assert_eq!(csv(10, &time_lock), Ok(false));
+
+        let tx = Transaction::from_hex("01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f00000000494830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be022040529b194ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee0121025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee635711000000").unwrap();
+        assert_eq!(ScriptContext::new(&tx, 0), ScriptContext { tx_version: 1, tx_locktime: 0x11, input_sequence: 0xffffffee, input_index: 0 });
+    }
+
+    #[test]
+    fn test_witness_program() {
+        let p2wpkh = script("0014751e76e8199196d454941c45d1b3a323f1433bd6");
+        assert_eq!(p2wpkh.witness_program(), Some((0, &from_hex("751e76e8199196d454941c45d1b3a323f1433bd6").unwrap()[..])));
+        assert_eq!(script(&format!("5120{}", "ab".repeat(32))).witness_program().map(|(version, _)| version), Some(1));
+        assert_eq!(script("6002aabb").witness_program().map(|(version, program)| (version, program.len())), Some((16, 2)));
+        assert_eq!(script("0001aa").witness_program(), None);
+        assert_eq!(script("76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac").witness_program(), None);
+    }
+
+    #[test]
+    fn test_is_unspendable() {
+        assert!(script("6a0401020304").is_unspendable());
+        assert!(Script::from_opcodes(&[OpCode::Return]).is_unspendable());
+        assert!(!script("76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac").is_unspendable());
+        assert!(Script::from_opcodes(&vec![OpCode::Push(vec![0; 520]); 20]).is_unspendable());
+    }
+
+    #[test]
+    fn test_witness_script_rules() {
+        // 2-of-2 multisig witness script
+        let multisig = script("52210375e00eb72e29da82b89367947f29ef34afb75e8654f6ea368e0acdfd92976b7c2103a1b26313f430c4b15bb1fdce663207659d8cac749a0e53d70eff01874496feff52ae");
+        assert_eq!(multisig.size(), multisig.to_bytes().len());
+        assert!(multisig.is_valid_witness_script());
+
+        let mut with_cat = multisig.clone();
+        with_cat.0.insert(0, OpCode::Cat.to_byte().unwrap());
+        assert!(!with_cat.is_valid_witness_script());
+        assert!(with_cat.contains_disabled_opcode());
+        assert!(!multisig.contains_disabled_opcode());
+        assert!(OpCode::Cat.is_disabled() && OpCode::RShift.is_disabled());
+        assert!(!OpCode::Add.is_disabled() && !OpCode::Push(vec![0x7e]).is_disabled());
+        let mut oversized = multisig.clone();
+        let filler = [OpCode::Push(vec![0; 100]), OpCode::Drop].iter().cycle().take(70).cloned().collect::<Vec<_>>();
+        oversized.0.extend(Script::from_opcodes(&filler).0);
+        assert!(oversized.size() > MAX_STANDARD_P2WSH_SCRIPT_SIZE);
+        assert!(!oversized.is_valid_witness_script());
+        assert!(oversized.is_consensus_valid_witness_script());
+        assert!(!with_cat.is_consensus_valid_witness_script());
+
+        let nops = |len: usize| Script(vec![OpCode::Nop.to_byte().unwrap(); len]);
+        assert!(nops(MAX_STANDARD_P2WSH_SCRIPT_SIZE).is_valid_witness_script());
+        assert!(!nops(MAX_STANDARD_P2WSH_SCRIPT_SIZE + 1).is_valid_witness_script());
+        assert!(nops(MAX_STANDARD_P2WSH_SCRIPT_SIZE + 1).is_consensus_valid_witness_script());
+        assert!(nops(MAX_SCRIPT_SIZE).is_consensus_valid_witness_script());
+        assert!(!nops(MAX_SCRIPT_SIZE + 1).is_consensus_valid_witness_script());
+
+        assert!(Script::minimal_if(&[]));
+        assert!(Script::minimal_if(&[0x01]));
+        assert!(!Script::minimal_if(&[0x02]));
+        assert!(!Script::minimal_if(&[0x00]));
+        assert!(!Script::minimal_if(&[0x01, 0x00]));
+    }
+
+    #[test]
+    fn test_op_return_data() {
+        let data = |asm: &str| Script::from_asm(asm).unwrap().op_return_data();
+        let concatenated = |asm: &str| Script::from_asm(asm).unwrap().op_return_data_concatenated();
+
+        assert_eq!(data("OP_RETURN 68656c6c6f"), Some(b"hello".to_vec()));
+        assert_eq!(data("OP_RETURN"), None);
+        assert_eq!(data("OP_RETURN 6865 6c6c6f"), None);
+        assert_eq!(data("OP_DUP 68656c6c6f"), None);
+
+        assert_eq!(concatenated("OP_RETURN 6865 6c6c6f"), Some(b"hello".to_vec()));
+        assert_eq!(concatenated("OP_RETURN"), None);
+        assert_eq!(concatenated("OP_RETURN 6865 OP_DUP"), None);
+    }
+
+    #[test]
+    fn test_parse_all_opcodes() {
+        for byte in (0x00..=0xff).filter(|b| !(0x01..=0x4e).contains(b)) {
+            let script = [byte];
+            let (opcode, bytes) = OpCode::parse(&script).unwrap();
+            assert!(bytes.is_empty());
+            assert_eq!(opcode.to_byte(), Some(byte));
+        }
+
+        assert_eq!(OpCode::parse(&[0x51]).map(|(opcode, _)| opcode), Ok(OpCode::num(1).unwrap()));
+        assert_eq!(OpCode::parse(&[0x60]).map(|(opcode, _)| opcode), Ok(OpCode::num(16).unwrap()));
+        assert!(matches!(OpCode::parse(&[0xae]), Ok((OpCode::CheckMultiSig, _))));
+        assert_eq!(OpCode::parse(&[0xff]).map(|(opcode, _)| opcode), Ok(OpCode::Unknown(UnknownOpCode::new(0xff).unwrap())));
+    }
+
+    #[test]
+    fn test_opcode_roundtrip() {
+        let encode = |opcode: &OpCode| {
+            let mut bytes = Vec::new();
+            opcode.encode(&mut bytes);
+            bytes
+        };
+
+        for byte in (0x00..=0xff).filter(|b| !(0x01..=0x4e).contains(b)) {
+            let opcode = OpCode::from_byte(byte).unwrap();
+            assert_eq!(encode(&opcode), [byte]);
+            assert_eq!(OpCode::parse_exact(&encode(&opcode)), Ok(opcode));
+        }
+        assert_eq!(encode(&OpCode::Return), [106]);
+        assert_eq!(encode(&OpCode::Dup), [118]);
+
+        // Numbers and unknown opcodes can only hold the bytes they parse from
+        let nums = (0..=0xff).filter_map(OpCode::num).collect::<Vec<_>>();
+        assert_eq!(nums.len(), 16);
+        let unknowns = (0..=0xff).filter_map(UnknownOpCode::new).map(OpCode::Unknown).collect::<Vec<_>>();
+        assert_eq!(unknowns.len(), 0x100 - 0xbb);
+        for opcode in nums.iter().chain(&unknowns) {
+            assert_eq!(OpCode::parse_exact(&encode(opcode)).as_ref(), Ok(opcode));
+        }
+        assert_eq!(SmallNum::try_from(17), Err(Error::InvalidSmallNum(17)));
+        assert_eq!(UnknownOpCode::try_from(0x76), Err(Error::AssignedOpCode(0x76)));
+
+        // Pushes use the shortest length prefix that doesn't make them another opcode
+        for (len, prefix) in [(0, vec![0x4c, 0x00]), (1, vec![0x01]), (75, vec![0x4b]), (76, vec![0x4c, 76]), (255, vec![0x4c, 0xff]), (256, vec![0x4d, 0x00, 0x01]), (0xffff, vec![0x4d, 0xff, 0xff]), (0x10000, vec![0x4e, 0x00, 0x00, 0x01, 0x00])] {
+            let opcode = OpCode::Push(vec![0xab; len]);
+            let bytes = encode(&opcode);
+            assert_eq!(bytes[..prefix.len()], prefix[..], "{}", len);
+            assert_eq!(bytes.len(), opcode.encoded_len());
+            assert_eq!(OpCode::parse_exact(&bytes), Ok(opcode));
+        }
+        assert_eq!(Script::from_opcodes(&[OpCode::Push(vec![])]).as_bytes(), [0x00]);
+    }
+}
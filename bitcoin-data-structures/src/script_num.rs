@@ -0,0 +1,90 @@
+//! Script numbers (`CScriptNum`): little-endian sign-magnitude integers used by the arithmetic
+//! opcodes, BIP34 heights and the lock time opcodes
+
+use crate::prelude::*;
+use crate::utils::Error;
+
+/// Operands of the arithmetic opcodes can be at most 4 bytes long
+pub const MAX_NUM_SIZE: usize = 4;
+/// `OP_CHECKLOCKTIMEVERIFY` and `OP_CHECKSEQUENCEVERIFY` accept 5 bytes to reach `2^39 - 1`
+pub const MAX_LOCKTIME_NUM_SIZE: usize = 5;
+
+/// Minimal encoding of `n`: empty for zero, sign in the top bit of the last byte
+pub fn encode(n: i64) -> Vec<u8> {
+    let negative = n < 0;
+    let mut data = n.unsigned_abs().to_le_bytes().to_vec();
+    while data.last() == Some(&0) {
+        data.pop();
+    }
+    match data.last() {
+        // The top bit is taken by the magnitude, the sign needs an extra byte
+        Some(last) if *last & 0x80 != 0 => data.push(if negative { 0x80 } else { 0x00 }),
+        Some(_) if negative => *data.last_mut().expect("not empty") |= 0x80,
+        _ => {},
+    }
+    data
+}
+
+/// Decodes a minimally-encoded operand of at most `MAX_NUM_SIZE` bytes
+pub fn decode(data: &[u8]) -> Result<i64, Error> {
+    decode_with_max_size(data, MAX_NUM_SIZE)
+}
+
+/// Like `decode` with a custom size limit, which can't be more than 8 bytes
+pub fn decode_with_max_size(data: &[u8], max_size: usize) -> Result<i64, Error> {
+    let max_size = max_size.min(8);
+    if data.len() > max_size {
+        return Err(Error::ScriptNumTooLarge { len: data.len(), max: max_size });
+    }
+    let (last, rest) = match data.split_last() {
+        Some(split) => split,
+        None => return Ok(0),
+    };
+    // The last byte can only be `0x00`/`0x80` if it's needed for the sign bit
+    if *last & 0x7f == 0 && rest.last().is_none_or(|b| *b & 0x80 == 0) {
+        return Err(Error::NonMinimalScriptNum);
+    }
+
+    let mut magnitude = [0; 8];
+    magnitude[..data.len()].copy_from_slice(data);
+    magnitude[rest.len()] &= 0x7f;
+    let magnitude = i64::from_le_bytes(magnitude);
+
+    Ok(if *last & 0x80 != 0 { -magnitude } else { magnitude })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(&[]), Ok(0));
+        assert_eq!(decode(&[0x01]), Ok(1));
+        assert_eq!(decode(&[0x81]), Ok(-1));
+        assert_eq!(decode(&[0x80, 0x00]), Ok(128));
+        assert_eq!(decode(&[0x80, 0x80]), Ok(-128));
+        assert_eq!(decode(&[0xb1, 0x47, 0x07]), Ok(477105));
+        assert_eq!(decode(&[0xff, 0xff, 0xff, 0xff]), Ok(-0x7fff_ffff));
+        // Non-minimal encodings
+        assert_eq!(decode(&[0x00]), Err(Error::NonMinimalScriptNum));
+        assert_eq!(decode(&[0x80]), Err(Error::NonMinimalScriptNum));
+        assert_eq!(decode(&[0x01, 0x00]), Err(Error::NonMinimalScriptNum));
+
+        assert_eq!(decode(&[0x01; 5]), Err(Error::ScriptNumTooLarge { len: 5, max: 4 }));
+        assert_eq!(decode_with_max_size(&[0xff, 0xff, 0xff, 0xff, 0x7f], MAX_LOCKTIME_NUM_SIZE), Ok(0x7f_ffff_ffff));
+        assert_eq!(decode_with_max_size(&[0x01; 9], 9), Err(Error::ScriptNumTooLarge { len: 9, max: 8 }));
+    }
+
+    #[test]
+    fn test_encode() {
+        for n in [0, 1, -1, 16, 127, 128, -128, 255, -255, 256, 0x7fff_ffff, -0x8000_0000, i64::MAX, i64::MIN + 1] {
+            assert_eq!(decode_with_max_size(&encode(n), 8), Ok(n), "{}", n);
+        }
+        assert_eq!(encode(0), Vec::<u8>::new());
+        assert_eq!(encode(-1), vec![0x81]);
+        assert_eq!(encode(128), vec![0x80, 0x00]);
+        assert_eq!(encode(-128), vec![0x80, 0x80]);
+        assert_eq!(encode(i64::MIN), vec![0, 0, 0, 0, 0, 0, 0, 0x80, 0x80]);
+    }
+}
@@ -0,0 +1,67 @@
+//! `#[serde(with = ...)]` helpers, byte strings are written as hex like `bitcoin-cli` does
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serializer};
+
+use crate::hashes::Txid;
+use crate::prelude::*;
+use crate::utils::*;
+
+/// A `[u8; 32]` hash in the byte-reversed form shown by block explorers
+pub(crate) mod reversed_hex {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(hash: &[u8; 32], s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(&Txid(*hash))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<[u8; 32], D::Error> {
+        let s = String::deserialize(d)?;
+        s.parse::<Txid>().map(|txid| txid.0).map_err(D::Error::custom)
+    }
+}
+
+pub(crate) mod hex_bytes {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&to_hex(&bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+        from_hex(&String::deserialize(d)?).map_err(D::Error::custom)
+    }
+}
+
+pub(crate) mod hex_bytes_opt {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(bytes: &Option<Vec<u8>>, s: S) -> Result<S::Ok, S::Error> {
+        match bytes {
+            Some(bytes) => hex_bytes::serialize(bytes, s),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Vec<u8>>, D::Error> {
+        Option::<String>::deserialize(d)?.map(|s| from_hex(&s).map_err(D::Error::custom)).transpose()
+    }
+}
+
+pub(crate) mod hex_bytes_vec {
+    use serde::ser::SerializeSeq;
+
+    use super::*;
+
+    pub fn serialize<S: Serializer>(items: &[Vec<u8>], s: S) -> Result<S::Ok, S::Error> {
+        let mut seq = s.serialize_seq(Some(items.len()))?;
+        for item in items {
+            seq.serialize_element(&to_hex(item))?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<Vec<u8>>, D::Error> {
+        Vec::<String>::deserialize(d)?.into_iter().map(|s| from_hex(&s).map_err(D::Error::custom)).collect()
+    }
+}
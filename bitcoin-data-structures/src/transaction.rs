@@ -0,0 +1,1357 @@
+use alloc::collections::BTreeSet;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io::Read;
+use core::str::FromStr;
+
+use crate::amount::Amount;
+use crate::encoding::*;
+use crate::hashes::{hash160, sha256, sha256d, Txid};
+use crate::prelude::*;
+use crate::script::{OpCode, OpCodeRef, Script, ScriptType};
+use crate::utils::*;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OutPoint {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_utils::reversed_hex"))]
+    pub txid: [u8; 32],
+    pub vout: u32,
+}
+
+impl OutPoint {
+    pub fn is_coinbase(&self) -> bool {
+        self.txid == [0; 32] && self.vout == 0xFFFFFFFF
+    }
+}
+
+/// `<txid>:<vout>`, with the txid in the usual reversed hex
+impl fmt::Display for OutPoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", Txid(self.txid), self.vout)
+    }
+}
+
+impl FromStr for OutPoint {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (txid, vout) = s.split_once(':').ok_or(Error::MissingOutPointSeparator)?;
+        let txid = txid.parse::<Txid>()?.0;
+        let vout = vout.parse().map_err(|_| Error::InvalidVout(vout.to_string()))?;
+
+        Ok(OutPoint { txid, vout })
+    }
+}
+
+impl Parse for OutPoint {
+    fn parse(bytes: &[u8]) -> Result<(Self, &[u8]), Error> {
+        let (txid, bytes) = Parse::parse(bytes)?;
+        let (vout, bytes) = Parse::parse(bytes)?;
+
+        let outpoint = OutPoint {
+            txid, vout
+        };
+
+        Ok((outpoint, bytes))
+    }
+}
+
+#[cfg(feature = "std")]
+impl Decode for OutPoint {
+    fn decode<R: Read>(r: &mut R) -> Result<Self, Error> {
+        Ok(OutPoint { txid: Decode::decode(r)?, vout: Decode::decode(r)? })
+    }
+}
+
+impl Encode for OutPoint {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.txid.encode(out);
+        self.vout.encode(out);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(from = "TxInJson", into = "TxInJson"))]
+pub struct TxIn {
+    pub previous_output: OutPoint,
+    /// For a coinbase, the arbitrary coinbase data, which doesn't have to be a valid script
+    pub script_sig: Script,
+    pub sequence: u32,
+    pub witness: Vec<Vec<u8>>,
+}
+
+/// `TxIn` as shown by `bitcoin-cli`, the scriptSig of a coinbase is the raw `coinbase` hex
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct TxInJson {
+    #[serde(flatten)]
+    previous_output: OutPoint,
+    #[serde(with = "crate::serde_utils::hex_bytes_opt", default, skip_serializing_if = "Option::is_none")]
+    coinbase: Option<Vec<u8>>,
+    #[serde(rename = "scriptSig", default, skip_serializing_if = "Option::is_none")]
+    script_sig: Option<Script>,
+    sequence: u32,
+    #[serde(rename = "txinwitness", with = "crate::serde_utils::hex_bytes_vec", default, skip_serializing_if = "Vec::is_empty")]
+    witness: Vec<Vec<u8>>,
+}
+
+#[cfg(feature = "serde")]
+impl From<TxIn> for TxInJson {
+    fn from(txin: TxIn) -> Self {
+        let (coinbase, script_sig) = if txin.is_coinbase() { (Some(txin.script_sig.0), None) } else { (None, Some(txin.script_sig)) };
+        TxInJson { previous_output: txin.previous_output, coinbase, script_sig, sequence: txin.sequence, witness: txin.witness }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<TxInJson> for TxIn {
+    fn from(json: TxInJson) -> Self {
+        let script_sig = json.coinbase.map(Script).or(json.script_sig).unwrap_or_default();
+        TxIn { previous_output: json.previous_output, script_sig, sequence: json.sequence, witness: json.witness }
+    }
+}
+
+impl TxIn {
+    /// Whether this is the input of a coinbase, spending the null outpoint
+    pub fn is_coinbase(&self) -> bool {
+        self.previous_output.is_coinbase()
+    }
+
+    /// For a P2SH spend of `script_pubkey`, the redeem script serialized in the last push of
+    /// the scriptSig. `None` for any other kind of spend, if it doesn't match the script hash or
+    /// isn't a valid script. The scriptSig alone can't tell, the last push of a P2PKH spend is
+    /// a public key that may well tokenize
+    pub fn redeem_script(&self, script_pubkey: &Script) -> Option<Script> {
+        if script_pubkey.classify() != ScriptType::P2sh || !self.script_sig.is_push_only() {
+            return None;
+        }
+
+        let redeem_script = Script(self.script_sig.push_bytes().last()?.to_vec());
+        let hash_matches = hash160(redeem_script.as_bytes()) == script_pubkey.as_bytes()[2..22];
+        (hash_matches && redeem_script.opcodes().is_ok()).then_some(redeem_script)
+    }
+
+    pub const SEQUENCE_LOCKTIME_DISABLE_FLAG: u32 = 1 << 31;
+    pub const SEQUENCE_LOCKTIME_TYPE_FLAG: u32 = 1 << 22;
+
+    /// BIP68 relative lock time encoded in the sequence, only enforced by version 2+ transactions
+    pub fn relative_locktime(&self) -> Option<RelativeLockTime> {
+        if self.sequence & Self::SEQUENCE_LOCKTIME_DISABLE_FLAG != 0 {
+            return None;
+        }
+
+        let value = self.sequence as u16;
+        Some(match self.sequence & Self::SEQUENCE_LOCKTIME_TYPE_FLAG {
+            0 => RelativeLockTime::Blocks(value),
+            _ => RelativeLockTime::Time(value),
+        })
+    }
+
+    /// The raw witness stack, empty for non-segwit inputs
+    pub fn witness(&self) -> &[Vec<u8>] {
+        &self.witness
+    }
+
+    /// For a P2WSH or P2SH-P2WSH spend of `script_pubkey`, the witness script serialized in the
+    /// last stack item. `None` for any other kind of spend, or if it doesn't match the program
+    pub fn witness_script(&self, script_pubkey: &Script) -> Option<Script> {
+        let program = match script_pubkey.classify() {
+            ScriptType::P2wsh if self.script_sig.0.is_empty() => script_pubkey.clone(),
+            ScriptType::P2sh => self.redeem_script(script_pubkey).filter(|redeem_script| redeem_script.classify() == ScriptType::P2wsh)?,
+            _ => return None,
+        };
+
+        let witness_script = self.witness.last()?;
+        (sha256(witness_script) == program.as_bytes()[2..]).then(|| Script(witness_script.clone()))
+    }
+}
+
+impl Parse for TxIn {
+    fn parse(bytes: &[u8]) -> Result<(Self, &[u8]), Error> {
+        TxIn::parse_with_limits(bytes, &ParseLimits::UNLIMITED)
+    }
+}
+
+impl TxIn {
+    fn parse_with_limits<'b>(bytes: &'b [u8], limits: &ParseLimits) -> Result<(Self, &'b [u8]), Error> {
+        let (previous_output, bytes) = OutPoint::parse(bytes)?;
+        let (script_sig, bytes) = Script::parse_with_max_len(bytes, limits.max_script_len, limits.varint_mode)?;
+        let (sequence, bytes) = Parse::parse(bytes)?;
+
+        // The witness is serialized after the outputs, `Transaction::parse` fills it in
+        let txin = TxIn {
+            previous_output,
+            script_sig,
+            sequence,
+            witness: vec![],
+        };
+
+        Ok((txin, bytes))
+    }
+}
+
+#[cfg(feature = "std")]
+impl Decode for TxIn {
+    fn decode<R: Read>(r: &mut R) -> Result<Self, Error> {
+        let previous_output = OutPoint::decode(r)?;
+        let script_sig = Decode::decode(r)?;
+        let sequence = Decode::decode(r)?;
+
+        Ok(TxIn { previous_output, script_sig, sequence, witness: vec![] })
+    }
+}
+
+impl Encode for TxIn {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.previous_output.encode(out);
+        self.script_sig.encode(out);
+        self.sequence.encode(out);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TxOut {
+    /// In satoshis
+    pub value: u64,
+    #[cfg_attr(feature = "serde", serde(rename = "scriptPubKey"))]
+    pub script_pubkey: Script,
+}
+
+impl TxOut {
+    pub fn amount(&self) -> Amount {
+        Amount(self.value)
+    }
+
+    /// Core's default `-dustrelayfee`, in sat/vB
+    pub const DUST_RELAY_FEE: u64 = 3;
+
+    /// Value below which the output costs more to spend than it's worth at `fee_rate` sat/vB,
+    /// like Core's `GetDustThreshold`: the size of the output plus the size of a typical input
+    /// spending it, 148 bytes or 67 vbytes for witness programs. Zero for unspendable outputs
+    pub fn dust_threshold(&self, fee_rate: u64) -> u64 {
+        if self.script_pubkey.is_unspendable() {
+            return 0;
+        }
+
+        let mut bytes = Vec::new();
+        self.encode(&mut bytes);
+        // Outpoint, scriptSig length, sequence and a 107 bytes signature + pubkey, which is
+        // witness data discounted by 4 for witness programs
+        let spend_size = match self.script_pubkey.witness_program() {
+            Some(_) => 32 + 4 + 1 + 107 / 4 + 4,
+            None => 32 + 4 + 1 + 107 + 4,
+        };
+        (bytes.len() as u64 + spend_size) * fee_rate
+    }
+
+    pub fn is_dust(&self, fee_rate: u64) -> bool {
+        self.value < self.dust_threshold(fee_rate)
+    }
+}
+
+impl Parse for TxOut {
+    fn parse(bytes: &[u8]) -> Result<(Self, &[u8]), Error> {
+        TxOut::parse_with_limits(bytes, &ParseLimits::UNLIMITED)
+    }
+}
+
+impl TxOut {
+    fn parse_with_limits<'b>(bytes: &'b [u8], limits: &ParseLimits) -> Result<(Self, &'b [u8]), Error> {
+        let (value, bytes) = Parse::parse(bytes)?;
+        let (script_pubkey, bytes) = Script::parse_with_max_len(bytes, limits.max_script_len, limits.varint_mode)?;
+
+        let txout = TxOut {
+            value, script_pubkey
+        };
+
+        Ok((txout, bytes))
+    }
+}
+
+#[cfg(feature = "std")]
+impl Decode for TxOut {
+    fn decode<R: Read>(r: &mut R) -> Result<Self, Error> {
+        Ok(TxOut { value: Decode::decode(r)?, script_pubkey: Decode::decode(r)? })
+    }
+}
+
+impl Encode for TxOut {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.value.encode(out);
+        self.script_pubkey.encode(out);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transaction {
+    pub version: u32,
+    #[cfg_attr(feature = "serde", serde(rename = "vin"))]
+    pub inputs: Vec<TxIn>,
+    #[cfg_attr(feature = "serde", serde(rename = "vout"))]
+    pub outputs: Vec<TxOut>,
+    pub locktime: u32,
+}
+
+impl Transaction {
+    pub const SIGHASH_ALL: u32 = 0x01;
+    pub const SIGHASH_NONE: u32 = 0x02;
+    pub const SIGHASH_SINGLE: u32 = 0x03;
+    pub const SIGHASH_ANYONECANPAY: u32 = 0x80;
+
+    /// Parses a standalone hex-encoded transaction such as `getrawtransaction` output, surrounding
+    /// whitespace is ignored
+    pub fn from_hex(s: &str) -> Result<Transaction, Error> {
+        Transaction::parse_exact(&from_hex(s.trim())?)
+    }
+
+    /// A coinbase has a single input spending the null outpoint
+    pub fn is_coinbase(&self) -> bool {
+        self.inputs.len() == 1 && self.inputs[0].previous_output.is_coinbase()
+    }
+
+    /// Sum of the output values, errors instead of wrapping around
+    pub fn total_output_value(&self) -> Result<u64, Error> {
+        self.outputs.iter().try_fold(0u64, |sum, txout| sum.checked_add(txout.value).ok_or(Error::ValueOverflow))
+    }
+
+    /// Input value minus output value, with `get_value` looking up the value of each spent
+    /// output. Errors if a prevout is unknown or the outputs exceed the inputs
+    pub fn fee<F: Fn(&OutPoint) -> Option<u64>>(&self, get_value: F) -> Result<u64, Error> {
+        let input_value = self.inputs.iter().try_fold(0u64, |sum, txin| {
+            let value = get_value(&txin.previous_output).ok_or_else(|| Error::UnknownPrevout(txin.previous_output.clone()))?;
+            sum.checked_add(value).ok_or(Error::ValueOverflow)
+        })?;
+        input_value.checked_sub(self.total_output_value()?).ok_or(Error::OutputsExceedInputs(self.txid()))
+    }
+
+    /// Highest version relayed by Core
+    pub const MAX_STANDARD_VERSION: u32 = 2;
+    pub const MAX_STANDARD_WEIGHT: usize = 400_000;
+    pub const MAX_STANDARD_SCRIPT_SIG_SIZE: usize = 1650;
+    /// Size limit of `OP_RETURN` outputs, the opcode included
+    pub const MAX_OP_RETURN_RELAY: usize = 83;
+
+    /// Core's `IsStandardTx` policy with the default settings, returning the first violation
+    pub fn is_standard(&self) -> Result<(), NonStandardReason> {
+        if !(1..=Self::MAX_STANDARD_VERSION).contains(&self.version) {
+            return Err(NonStandardReason::Version(self.version));
+        }
+        let weight = self.weight();
+        if weight > Self::MAX_STANDARD_WEIGHT {
+            return Err(NonStandardReason::Weight(weight));
+        }
+
+        for (index, txin) in self.inputs.iter().enumerate() {
+            if txin.script_sig.size() > Self::MAX_STANDARD_SCRIPT_SIG_SIZE {
+                return Err(NonStandardReason::ScriptSigSize(index));
+            }
+            if !txin.script_sig.is_push_only() {
+                return Err(NonStandardReason::ScriptSigNotPushOnly(index));
+            }
+        }
+
+        let mut op_returns = 0;
+        for (index, txout) in self.outputs.iter().enumerate() {
+            match txout.script_pubkey.classify() {
+                ScriptType::NonStandard => return Err(NonStandardReason::ScriptPubKey(index)),
+                ScriptType::Multisig if txout.script_pubkey.as_multisig().is_some_and(|(_, keys)| keys.len() > 3) => {
+                    return Err(NonStandardReason::BareMultisig(index));
+                },
+                ScriptType::OpReturn => {
+                    if txout.script_pubkey.size() > Self::MAX_OP_RETURN_RELAY {
+                        return Err(NonStandardReason::DataCarrierSize(index));
+                    }
+                    op_returns += 1;
+                },
+                _ if txout.is_dust(TxOut::DUST_RELAY_FEE) => return Err(NonStandardReason::Dust(index)),
+                _ => {},
+            }
+        }
+        if op_returns > 1 {
+            return Err(NonStandardReason::MultiOpReturn);
+        }
+
+        Ok(())
+    }
+
+    /// `locktime` values below this are block heights, the others are Unix timestamps
+    pub const LOCKTIME_THRESHOLD: u32 = 500_000_000;
+
+    /// The lock time, which is only enforced if at least one input has a non-final sequence
+    pub fn lock_time(&self) -> LockTime {
+        if self.inputs.iter().all(|txin| txin.sequence == 0xFFFFFFFF) {
+            LockTime::Disabled
+        } else if self.locktime < Self::LOCKTIME_THRESHOLD {
+            LockTime::Height(self.locktime)
+        } else {
+            LockTime::Time(self.locktime)
+        }
+    }
+
+    /// BIP125 opt-in replaceability: some input has a sequence below `0xFFFFFFFE`. Only the
+    /// explicit signal, a descendant of a signaling transaction is replaceable too
+    pub fn is_rbf_signaling(&self) -> bool {
+        self.inputs.iter().any(|txin| txin.sequence < 0xFFFFFFFE)
+    }
+
+    /// Whether an outpoint is spent twice. Core 0.15 to 0.16.2 skipped this check for blocks
+    /// (CVE-2018-17144), so a miner could have inflated the supply with such a transaction
+    pub fn has_duplicate_inputs(&self) -> bool {
+        let mut seen = BTreeSet::new();
+        !self.inputs.iter().all(|txin| seen.insert(&txin.previous_output))
+    }
+
+    pub fn input_count(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn output_count(&self) -> usize {
+        self.outputs.len()
+    }
+
+    /// The output an `OutPoint` of this transaction refers to, `None` if `vout` is out of range
+    pub fn get_output(&self, vout: u32) -> Option<&TxOut> {
+        self.output_at(usize::try_from(vout).ok()?)
+    }
+
+    pub fn output_at(&self, index: usize) -> Option<&TxOut> {
+        self.outputs.get(index)
+    }
+
+    pub fn summary(&self) -> TxSummary {
+        TxSummary {
+            txid: self.txid(),
+            total_size: self.total_size(),
+            vsize: self.vsize(),
+            weight: self.weight(),
+            input_count: self.input_count(),
+            output_count: self.output_count(),
+            total_output_value: self.total_output_value().ok(),
+            is_coinbase: self.is_coinbase(),
+            is_segwit: self.has_witness(),
+        }
+    }
+
+    pub fn has_witness(&self) -> bool {
+        self.inputs.iter().any(|txin| !txin.witness.is_empty())
+    }
+
+    /// Legacy serialization: no marker, flag or witness even if the transaction has them
+    pub fn encode_without_witness(&self, out: &mut Vec<u8>) {
+        self.version.encode(out);
+        self.inputs.encode(out);
+        self.outputs.encode(out);
+        self.locktime.encode(out);
+    }
+
+    /// Size of the legacy serialization, without marker, flag and witness
+    pub fn base_size(&self) -> usize {
+        let mut bytes = Vec::new();
+        self.encode_without_witness(&mut bytes);
+        bytes.len()
+    }
+
+    /// Size of the full serialization, equal to `base_size` for transactions without witness
+    pub fn total_size(&self) -> usize {
+        let mut bytes = Vec::new();
+        self.encode(&mut bytes);
+        bytes.len()
+    }
+
+    /// BIP141 weight: `base_size * 3 + total_size`
+    pub fn weight(&self) -> usize {
+        self.base_size() * 3 + self.total_size()
+    }
+
+    /// Virtual size, the weight divided by 4 rounded up
+    pub fn vsize(&self) -> usize {
+        self.weight().div_ceil(4)
+    }
+
+    /// Pre-segwit signature hash of `input_index` as computed by Core's `SignatureHash`.
+    /// `OP_CODESEPARATOR`s are removed from `script_code`. An out of range input, or an
+    /// out of range output with `SIGHASH_SINGLE`, hashes to `1` like in consensus
+    pub fn legacy_sighash(&self, input_index: usize, script_code: &Script, sighash_type: u32) -> [u8; 32] {
+        let base_type = sighash_type & 0x1f;
+        if input_index >= self.inputs.len() || (base_type == Self::SIGHASH_SINGLE && input_index >= self.outputs.len()) {
+            let mut one = [0; 32];
+            one[0] = 0x01;
+            return one;
+        }
+
+        // Only whole opcodes are removed, the bytes of the others are kept as they are
+        let separators = script_code
+            .iter()
+            .map_while(Result::ok)
+            .filter(|(_, opcode)| *opcode == OpCodeRef::Op(OpCode::CodeSeparator))
+            .map(|(offset, _)| offset)
+            .collect::<Vec<_>>();
+        let script_code = Script(script_code.0.iter().enumerate().filter(|(offset, _)| !separators.contains(offset)).map(|(_, byte)| *byte).collect());
+        let blank_input = |index: usize, txin: &TxIn| TxIn {
+            previous_output: txin.previous_output.clone(),
+            script_sig: if index == input_index { script_code.clone() } else { Script(vec![]) },
+            // With NONE and SINGLE the other inputs can be updated freely
+            sequence: match base_type {
+                Self::SIGHASH_NONE | Self::SIGHASH_SINGLE if index != input_index => 0,
+                _ => txin.sequence,
+            },
+            witness: vec![],
+        };
+        let inputs = match sighash_type & Self::SIGHASH_ANYONECANPAY {
+            0 => self.inputs.iter().enumerate().map(|(index, txin)| blank_input(index, txin)).collect(),
+            _ => vec![blank_input(input_index, &self.inputs[input_index])],
+        };
+        let outputs = match base_type {
+            Self::SIGHASH_NONE => vec![],
+            // Outputs before the signed one are blanked to a value of -1 and an empty script
+            Self::SIGHASH_SINGLE => (0..=input_index)
+                .map(|index| match index == input_index {
+                    true => self.outputs[index].clone(),
+                    false => TxOut { value: u64::MAX, script_pubkey: Script(vec![]) },
+                })
+                .collect(),
+            _ => self.outputs.clone(),
+        };
+
+        let tx = Transaction { version: self.version, inputs, outputs, locktime: self.locktime };
+        let mut bytes = Vec::new();
+        tx.encode_without_witness(&mut bytes);
+        sighash_type.encode(&mut bytes);
+        sha256d(&bytes)
+    }
+
+    /// BIP143 signature hash of a segwit v0 input spending `value` satoshis. Signing several
+    /// inputs through a `SighashCache` avoids recomputing the shared hashes every time
+    pub fn segwit_sighash(&self, input_index: usize, script_code: &Script, value: u64, sighash_type: u32) -> [u8; 32] {
+        SighashCache::new(self).segwit_sighash(input_index, script_code, value, sighash_type)
+    }
+
+    pub fn txid(&self) -> Txid {
+        let mut bytes = Vec::new();
+        self.encode_without_witness(&mut bytes);
+        Txid(sha256d(&bytes))
+    }
+
+    /// BIP141 witness txid, equal to `txid()` for transactions without witness
+    pub fn wtxid(&self) -> Txid {
+        let mut bytes = Vec::new();
+        self.encode(&mut bytes);
+        Txid(sha256d(&bytes))
+    }
+}
+
+/// The figures of `Transaction::summary`, one row of a table of transactions
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TxSummary {
+    pub txid: Txid,
+    pub total_size: usize,
+    pub vsize: usize,
+    pub weight: usize,
+    pub input_count: usize,
+    pub output_count: usize,
+    /// `None` if the sum overflows
+    pub total_output_value: Option<u64>,
+    pub is_coinbase: bool,
+    pub is_segwit: bool,
+}
+
+/// Interpretation of `Transaction::locktime`, see `Transaction::lock_time`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LockTime {
+    /// Can't be mined before this block height
+    Height(u32),
+    /// Can't be mined before this median time past
+    Time(u32),
+    /// Every input has a final sequence, the lock time is ignored
+    Disabled,
+}
+
+/// BIP68 lock relative to the confirmation of the output being spent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RelativeLockTime {
+    Blocks(u16),
+    /// In units of 512 seconds
+    Time(u16),
+}
+
+/// Why `Transaction::is_standard` rejected a transaction, with the index of the offending input
+/// or output where relevant
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NonStandardReason {
+    Version(u32),
+    Weight(usize),
+    ScriptSigSize(usize),
+    ScriptSigNotPushOnly(usize),
+    ScriptPubKey(usize),
+    BareMultisig(usize),
+    DataCarrierSize(usize),
+    MultiOpReturn,
+    Dust(usize),
+}
+
+impl fmt::Display for NonStandardReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NonStandardReason::Version(version) => write!(f, "Non-standard version {}", version),
+            NonStandardReason::Weight(weight) => write!(f, "Weight {} exceeds the standard limit", weight),
+            NonStandardReason::ScriptSigSize(index) => write!(f, "Oversized scriptSig in input {}", index),
+            NonStandardReason::ScriptSigNotPushOnly(index) => write!(f, "scriptSig of input {} is not push-only", index),
+            NonStandardReason::ScriptPubKey(index) => write!(f, "Non-standard scriptPubKey in output {}", index),
+            NonStandardReason::BareMultisig(index) => write!(f, "Bare multisig with more than 3 keys in output {}", index),
+            NonStandardReason::DataCarrierSize(index) => write!(f, "Oversized OP_RETURN in output {}", index),
+            NonStandardReason::MultiOpReturn => write!(f, "More than one OP_RETURN output"),
+            NonStandardReason::Dust(index) => write!(f, "Dust output {}", index),
+        }
+    }
+}
+
+/// The BIP143 `hashPrevouts`, `hashSequence` and `hashOutputs` of a transaction, computed once
+/// and shared by the sighashes of all its inputs
+#[derive(Debug, Clone)]
+pub struct SighashCache<'a> {
+    tx: &'a Transaction,
+    hash_prevouts: [u8; 32],
+    hash_sequence: [u8; 32],
+    hash_outputs: [u8; 32],
+}
+
+impl<'a> SighashCache<'a> {
+    pub fn new(tx: &'a Transaction) -> Self {
+        let (mut prevouts, mut sequences, mut outputs) = (Vec::new(), Vec::new(), Vec::new());
+        for txin in &tx.inputs {
+            txin.previous_output.encode(&mut prevouts);
+            txin.sequence.encode(&mut sequences);
+        }
+        for txout in &tx.outputs {
+            txout.encode(&mut outputs);
+        }
+
+        SighashCache {
+            tx,
+            hash_prevouts: sha256d(&prevouts),
+            hash_sequence: sha256d(&sequences),
+            hash_outputs: sha256d(&outputs),
+        }
+    }
+
+    /// See `Transaction::segwit_sighash`. Panics if `input_index` is out of range
+    pub fn segwit_sighash(&self, input_index: usize, script_code: &Script, value: u64, sighash_type: u32) -> [u8; 32] {
+        let txin = &self.tx.inputs[input_index];
+        let base_type = sighash_type & 0x1f;
+        let anyone_can_pay = sighash_type & Transaction::SIGHASH_ANYONECANPAY != 0;
+
+        let hash_prevouts = if anyone_can_pay { [0; 32] } else { self.hash_prevouts };
+        let hash_sequence = match base_type {
+            _ if anyone_can_pay => [0; 32],
+            Transaction::SIGHASH_SINGLE | Transaction::SIGHASH_NONE => [0; 32],
+            _ => self.hash_sequence,
+        };
+        let hash_outputs = match (base_type, self.tx.outputs.get(input_index)) {
+            (Transaction::SIGHASH_SINGLE, Some(txout)) => {
+                let mut output = Vec::new();
+                txout.encode(&mut output);
+                sha256d(&output)
+            },
+            (Transaction::SIGHASH_SINGLE, None) | (Transaction::SIGHASH_NONE, _) => [0; 32],
+            _ => self.hash_outputs,
+        };
+
+        let mut bytes = Vec::new();
+        self.tx.version.encode(&mut bytes);
+        bytes.extend_from_slice(&hash_prevouts);
+        bytes.extend_from_slice(&hash_sequence);
+        txin.previous_output.encode(&mut bytes);
+        script_code.encode(&mut bytes);
+        value.encode(&mut bytes);
+        txin.sequence.encode(&mut bytes);
+        bytes.extend_from_slice(&hash_outputs);
+        self.tx.locktime.encode(&mut bytes);
+        sighash_type.encode(&mut bytes);
+        sha256d(&bytes)
+    }
+}
+
+/// Same as `Transaction::from_hex`
+impl FromStr for Transaction {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Transaction::from_hex(s)
+    }
+}
+
+/// Same as `Transaction::parse_exact`
+impl TryFrom<&[u8]> for Transaction {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Transaction::parse_exact(bytes)
+    }
+}
+
+impl Parse for Transaction {
+    fn parse(bytes: &[u8]) -> Result<(Self, &[u8]), Error> {
+        Transaction::parse_with_limits(bytes, &ParseLimits::UNLIMITED)
+    }
+}
+
+impl Transaction {
+    /// Like `parse`, enforcing the per transaction `limits` and their `varint_mode`
+    pub fn parse_with_limits<'b>(bytes: &'b [u8], limits: &ParseLimits) -> Result<(Self, &'b [u8]), Error> {
+        let (version, bytes) = Parse::parse(bytes)?;
+        // BIP144: a `0x00` marker (which would otherwise be an empty input vector) followed
+        // by a `0x01` flag signals that witness data follows the outputs
+        let (segwit, bytes) = match bytes {
+            [0x00, 0x01, bytes @ ..] => (true, bytes),
+            _ => (false, bytes),
+        };
+        let mode = limits.varint_mode;
+        let (mut inputs, bytes) = parse_vec_with(bytes, limits.max_inputs, mode, |bytes| TxIn::parse_with_limits(bytes, limits))?;
+        let (outputs, mut bytes) = parse_vec_with(bytes, limits.max_outputs, mode, |bytes| TxOut::parse_with_limits(bytes, limits))?;
+        if segwit {
+            for txin in &mut inputs {
+                let (witness, remainder) = parse_vec_with(bytes, limits.max_witness_items, mode, |bytes| {
+                    parse_bytes_with_limit(bytes, limits.max_witness_item_len, mode)
+                })?;
+                txin.witness = witness;
+                bytes = remainder;
+            }
+            // Like Core, reject the marker when it is not needed: `Encode` would leave it out
+            if inputs.iter().all(|txin| txin.witness.is_empty()) {
+                return Err(Error::SuperfluousWitness);
+            }
+        }
+        let (locktime, bytes) = Parse::parse(bytes)?;
+
+        let tx = Transaction {
+            version, inputs, outputs, locktime
+        };
+
+        Ok((tx, bytes))
+    }
+}
+
+#[cfg(feature = "std")]
+impl Decode for Transaction {
+    fn decode<R: Read>(r: &mut R) -> Result<Self, Error> {
+        let version = Decode::decode(r)?;
+        // Without lookahead the marker is read as an empty input vector, the next byte then
+        // tells whether it was really a marker or the start of the outputs
+        let mut inputs: Vec<TxIn> = Decode::decode(r)?;
+        let mut segwit = false;
+        let outputs = if inputs.is_empty() {
+            match u8::decode(r)? {
+                0x01 => {
+                    segwit = true;
+                    inputs = Decode::decode(r)?;
+                    Decode::decode(r)?
+                },
+                first => Decode::decode(&mut [first].chain(&mut *r))?,
+            }
+        } else {
+            Decode::decode(r)?
+        };
+        if segwit {
+            for txin in &mut inputs {
+                txin.witness = Decode::decode(r)?;
+            }
+            if inputs.iter().all(|txin| txin.witness.is_empty()) {
+                return Err(Error::SuperfluousWitness);
+            }
+        }
+        let locktime = Decode::decode(r)?;
+
+        Ok(Transaction { version, inputs, outputs, locktime })
+    }
+}
+
+impl Encode for Transaction {
+    fn encode(&self, out: &mut Vec<u8>) {
+        if !self.has_witness() {
+            return self.encode_without_witness(out);
+        }
+
+        self.version.encode(out);
+        out.extend_from_slice(&[0x00, 0x01]);
+        self.inputs.encode(out);
+        self.outputs.encode(out);
+        for txin in &self.inputs {
+            txin.witness.encode(out);
+        }
+        self.locktime.encode(out);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Signed P2WPKH example from BIP143
+    const BIP143_P2WPKH_TX: &str = "01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f00000000494830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be022040529b194ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee0121025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee635711000000";
+
+    // Block 170, the first transaction spending a non-coinbase output
+    const BLOCK_170_TX: &str = "0100000001c997a5e56e104102fa209c6a852dd90660a20b2d9c352423edce25857fcd3704000000004847304402204e45e16932b8af514961a1d3a1a25fdf3f4f7732e9d624c6c61548ab5fb8cd410220181522ec8eca07de4860a4acdd12909d831cc56cbbac4622082221a8768d1d0901ffffffff0200ca9a3b00000000434104ae1a62fe09c5f51b13905f07f06b99a2f7159b2225f374cd378d71302fa28414e7aab37397f554a7df5f142c21c1b7303b8a0626f1baded5c72a704f7e6cd84cac00286bee0000000043410411db93e1dcdb8a016b49840f8c53bc1eb68a382e97b1482ecad7b148a6909a5cb2e0eaddfb84ccf9744464f82e160bfa9b8b64f9d4c03f999b8643f656b412a3ac00000000";
+
+    #[test]
+    fn test_segwit_transaction() {
+        let tx_bytes = from_hex(BIP143_P2WPKH_TX).unwrap();
+        let (tx, bytes) = Transaction::parse(&tx_bytes).unwrap();
+        assert!(bytes.is_empty());
+
+        assert!(tx.has_witness());
+        assert_eq!(tx.inputs.len(), 2);
+        assert_eq!(tx.outputs.len(), 2);
+        assert!(tx.inputs[0].witness.is_empty());
+        assert_eq!(tx.inputs[1].witness.len(), 2);
+        assert_eq!(tx.inputs[1].witness[1], from_hex("025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee6357").unwrap());
+        assert_eq!(tx.locktime, 0x11);
+
+        let mut encoded = Vec::new();
+        tx.encode(&mut encoded);
+        assert_bytes_eq(&encoded, &tx_bytes);
+        // The witness ends with the 33 bytes public key and the 4 bytes lock time
+        assert_eq!(Transaction::parse(&tx_bytes[..tx_bytes.len() - 5]), Err(Error::InsufficientBytes { needed: 33, got: 32 }));
+
+        // The marker and flag with only empty witnesses have no encoding of their own
+        let (legacy_tx, _) = Transaction::parse(&from_hex(BLOCK_170_TX).unwrap()).unwrap();
+        let mut superfluous = Vec::new();
+        legacy_tx.version.encode(&mut superfluous);
+        superfluous.extend_from_slice(&[0x00, 0x01]);
+        legacy_tx.inputs.encode(&mut superfluous);
+        legacy_tx.outputs.encode(&mut superfluous);
+        superfluous.extend(legacy_tx.inputs.iter().map(|_| 0x00));
+        legacy_tx.locktime.encode(&mut superfluous);
+        assert_eq!(Transaction::parse(&superfluous), Err(Error::SuperfluousWitness));
+        #[cfg(feature = "std")]
+        assert_eq!(Transaction::decode(&mut &superfluous[..]), Err(Error::SuperfluousWitness));
+    }
+
+    #[test]
+    fn test_from_hex() {
+        let tx = Transaction::from_hex(&format!("{}\n", BIP143_P2WPKH_TX)).unwrap();
+        assert!(tx.has_witness());
+        assert_eq!(Transaction::parse_exact(&from_hex(BIP143_P2WPKH_TX).unwrap()), Ok(tx));
+
+        assert_eq!(Transaction::from_hex(&format!("{}00", BIP143_P2WPKH_TX)), Err(Error::TrailingBytes(1)));
+        assert_eq!(Transaction::from_hex(&BIP143_P2WPKH_TX[1..]), Err(Error::OddHexLength));
+    }
+
+    #[test]
+    fn test_legacy_sighash() {
+        // Block 170 spends the P2PK coinbase output of block 9
+        let tx = Transaction::from_hex(BLOCK_170_TX).unwrap();
+        let script_code = Script::from_bytes(&from_hex("410411db93e1dcdb8a016b49840f8c53bc1eb68a382e97b1482ecad7b148a6909a5cb2e0eaddfb84ccf9744464f82e160bfa9b8b64f9d4c03f999b8643f656b412a3ac").unwrap()).unwrap();
+        let sighash = tx.legacy_sighash(0, &script_code, Transaction::SIGHASH_ALL);
+        assert_eq!(to_hex(&sighash), "7a05c6145f10101e9d6325494245adf1297d80f8f38d4d576d57cdba220bcb19");
+
+        let mut with_separator = script_code.clone();
+        // Between the pubkey push and the `OP_CHECKSIG`
+        with_separator.0.insert(66, OpCode::CodeSeparator.to_byte().unwrap());
+        assert_eq!(tx.legacy_sighash(0, &with_separator, Transaction::SIGHASH_ALL), sighash);
+
+        // SIGHASH_SINGLE without a matching output signs the number one
+        let tx = Transaction::from_hex(BIP143_P2WPKH_TX).unwrap();
+        let mut one = [0; 32];
+        one[0] = 0x01;
+        assert_eq!(tx.legacy_sighash(2, &script_code, Transaction::SIGHASH_SINGLE), one);
+        assert_eq!(tx.legacy_sighash(5, &script_code, Transaction::SIGHASH_ALL), one);
+        assert_ne!(tx.legacy_sighash(1, &script_code, Transaction::SIGHASH_SINGLE), one);
+
+        // NONE doesn't commit to the outputs, ANYONECANPAY to the other inputs
+        let mut modified = tx.clone();
+        modified.outputs.pop();
+        modified.inputs[1].sequence = 0;
+        assert_eq!(modified.legacy_sighash(0, &script_code, Transaction::SIGHASH_NONE), tx.legacy_sighash(0, &script_code, Transaction::SIGHASH_NONE));
+        assert_ne!(modified.legacy_sighash(0, &script_code, Transaction::SIGHASH_ALL), tx.legacy_sighash(0, &script_code, Transaction::SIGHASH_ALL));
+        let mut modified = tx.clone();
+        modified.inputs.pop();
+        let sighash_type = Transaction::SIGHASH_ALL | Transaction::SIGHASH_ANYONECANPAY;
+        assert_eq!(modified.legacy_sighash(0, &script_code, sighash_type), tx.legacy_sighash(0, &script_code, sighash_type));
+        assert_ne!(tx.legacy_sighash(0, &script_code, sighash_type), tx.legacy_sighash(0, &script_code, Transaction::SIGHASH_ALL));
+    }
+
+    #[test]
+    fn test_segwit_sighash() {
+        // Native P2WPKH example from BIP143, the second input spends 6 BTC
+        let tx = Transaction::from_hex(BIP143_P2WPKH_TX).unwrap();
+        let cache = SighashCache::new(&tx);
+        assert_eq!(to_hex(&cache.hash_prevouts), "96b827c8483d4e9b96712b6713a7b68d6e8003a781feba36c31143470b4efd37");
+        assert_eq!(to_hex(&cache.hash_sequence), "52b0a642eea2fb7ae638c36f6252b6750293dbe574a806984b8e4d8548339a3b");
+        assert_eq!(to_hex(&cache.hash_outputs), "863ef3e1a92afbfdb97f31ad0fc7683ee943e9abcf2501590ff8f6551f47e5e5");
+
+        let script_code = Script::from_bytes(&from_hex("76a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac").unwrap()).unwrap();
+        let sighash = tx.segwit_sighash(1, &script_code, 600_000_000, Transaction::SIGHASH_ALL);
+        assert_eq!(to_hex(&sighash), "c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670");
+        assert_eq!(cache.segwit_sighash(1, &script_code, 600_000_000, Transaction::SIGHASH_ALL), sighash);
+        assert_ne!(tx.segwit_sighash(1, &script_code, 600_000_001, Transaction::SIGHASH_ALL), sighash);
+
+        // SINGLE only commits to the output at the same index, the other ones can change
+        let mut modified = tx.clone();
+        modified.outputs[0].value += 1;
+        let sighash_type = Transaction::SIGHASH_SINGLE | Transaction::SIGHASH_ANYONECANPAY;
+        assert_eq!(modified.segwit_sighash(1, &script_code, 1, sighash_type), tx.segwit_sighash(1, &script_code, 1, sighash_type));
+        assert_ne!(modified.segwit_sighash(0, &script_code, 1, sighash_type), tx.segwit_sighash(0, &script_code, 1, sighash_type));
+        assert_eq!(modified.segwit_sighash(1, &script_code, 1, Transaction::SIGHASH_NONE), tx.segwit_sighash(1, &script_code, 1, Transaction::SIGHASH_NONE));
+    }
+
+    #[test]
+    fn test_from_str() {
+        let tx = BIP143_P2WPKH_TX.parse::<Transaction>().unwrap();
+        assert_eq!(Ok(tx), Transaction::from_hex(BIP143_P2WPKH_TX));
+        assert_eq!(format!("{}00", BLOCK_170_TX).parse::<Transaction>(), Err(Error::TrailingBytes(1)));
+        assert_eq!("0x".parse::<Transaction>(), Err(Error::InvalidHexDigit('x')));
+
+        let tx_bytes = from_hex(BLOCK_170_TX).unwrap();
+        assert_eq!(Transaction::try_from(&tx_bytes[..]), Transaction::from_hex(BLOCK_170_TX));
+        assert_eq!(Transaction::try_from(&tx_bytes[..tx_bytes.len() - 1]), Err(Error::InsufficientBytes { needed: 4, got: 3 }));
+    }
+
+    #[test]
+    fn test_txid() {
+        let tx_bytes = from_hex(BLOCK_170_TX).unwrap();
+        let (tx, _) = Transaction::parse(&tx_bytes).unwrap();
+        assert_eq!(tx.txid().to_string(), "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16");
+        assert_eq!(tx.wtxid(), tx.txid());
+    }
+
+    #[test]
+    fn test_wtxid() {
+        let tx_bytes = from_hex(BIP143_P2WPKH_TX).unwrap();
+        let (tx, _) = Transaction::parse(&tx_bytes).unwrap();
+        assert_eq!(tx.wtxid(), Txid(sha256d(&tx_bytes)));
+        assert_ne!(tx.wtxid(), tx.txid());
+    }
+
+    #[test]
+    fn test_parse_with_limits() {
+        let bytes = from_hex(BIP143_P2WPKH_TX).unwrap();
+        let tx = Transaction::parse_exact(&bytes).unwrap();
+        let parse = |limits: ParseLimits| Transaction::parse_with_limits(&bytes, &limits).map(|(tx, _)| tx);
+        assert_eq!(parse(ParseLimits::default()), Ok(tx.clone()));
+
+        // The second input has a signature and a public key in its witness
+        let sig_len = tx.inputs[1].witness[0].len();
+        assert_eq!(parse(ParseLimits { max_witness_items: 1, ..ParseLimits::UNLIMITED }), Err(Error::LimitExceeded { got: 2, max: 1 }));
+        let limits = ParseLimits { max_witness_item_len: sig_len - 1, ..ParseLimits::UNLIMITED };
+        assert_eq!(parse(limits), Err(Error::LimitExceeded { got: sig_len as u64, max: sig_len - 1 }));
+
+        // The claimed length is checked before reading the script
+        let legacy = Transaction::from_hex(BLOCK_170_TX).unwrap();
+        let mut huge_script = Vec::new();
+        legacy.version.encode(&mut huge_script);
+        legacy.inputs.encode(&mut huge_script);
+        VarInt(1).encode(&mut huge_script);
+        legacy.outputs[0].value.encode(&mut huge_script);
+        huge_script.extend_from_slice(&[0xfe, 0xff, 0xff, 0xff, 0xff]);
+        let limits = ParseLimits { max_script_len: 10_000, ..ParseLimits::UNLIMITED };
+        assert_eq!(Transaction::parse_with_limits(&huge_script, &limits).err(), Some(Error::LimitExceeded { got: 0xffffffff, max: 10_000 }));
+    }
+
+    #[test]
+    fn test_non_minimal_push_roundtrip() {
+        let mut tx = Transaction::from_hex(BLOCK_170_TX).unwrap();
+        tx.inputs[0].script_sig = Script(from_hex("4c0107").unwrap());
+        // Consensus-valid in an output, it just can't be spent
+        tx.outputs[0].script_pubkey = Script(from_hex("76a94c").unwrap());
+
+        let mut bytes = Vec::new();
+        tx.encode(&mut bytes);
+        let parsed = Transaction::parse_exact(&bytes).unwrap();
+        assert_eq!(parsed, tx);
+        let mut encoded = Vec::new();
+        parsed.encode(&mut encoded);
+        assert_eq!(encoded, bytes);
+        assert_eq!(parsed.txid(), Txid(sha256d(&bytes)));
+        assert_eq!(parsed.total_size(), bytes.len());
+    }
+
+    #[test]
+    fn test_txid_excludes_witness() {
+        let tx_bytes = from_hex(BIP143_P2WPKH_TX).unwrap();
+        let (mut tx, _) = Transaction::parse(&tx_bytes).unwrap();
+        let txid = tx.txid();
+
+        let mut legacy_bytes = Vec::new();
+        tx.encode_without_witness(&mut legacy_bytes);
+        assert_eq!(txid, Txid(sha256d(&legacy_bytes)));
+
+        for txin in &mut tx.inputs {
+            txin.witness.clear();
+        }
+        assert_eq!(tx.txid(), txid);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_decode() {
+        let tx_bytes = from_hex(BIP143_P2WPKH_TX).unwrap();
+        let (tx, _) = Transaction::parse(&tx_bytes).unwrap();
+        let mut reader = &tx_bytes[..];
+        assert_eq!(Transaction::decode(&mut reader), Ok(tx));
+        assert!(reader.is_empty());
+
+        let mut reader = &tx_bytes[..tx_bytes.len() - 1];
+        assert_eq!(Transaction::decode(&mut reader), Err(Error::Io(std::io::ErrorKind::UnexpectedEof)));
+    }
+
+    #[test]
+    fn test_is_coinbase() {
+        let block = crate::Block::from_hex_str(include_str!("../block.hex")).unwrap();
+        assert!(block.transactions[0].is_coinbase());
+        assert!(block.transactions[1..].iter().all(|tx| !tx.is_coinbase()));
+
+        let (tx, _) = Transaction::parse(&from_hex(BIP143_P2WPKH_TX).unwrap()).unwrap();
+        assert!(!tx.is_coinbase());
+    }
+
+    #[test]
+    fn test_coinbase_script() {
+        let block = crate::Block::from_hex_str(include_str!("../block.hex")).unwrap();
+        let coinbase = &block.transactions[0];
+        assert!(coinbase.inputs[0].is_coinbase());
+        assert_eq!(coinbase.inputs[0].script_sig.as_bytes()[..4], [0x03, 0xb1, 0x47, 0x07]);
+        assert!(block.transactions[1].inputs.iter().all(|txin| !txin.is_coinbase()));
+
+        // The coinbase data is kept as is, even when it doesn't tokenize
+        let mut truncated_push = coinbase.clone();
+        truncated_push.inputs[0].script_sig.0.push(0x4c);
+        for coinbase in [coinbase, &truncated_push] {
+            let mut encoded = Vec::new();
+            coinbase.encode(&mut encoded);
+            assert_eq!(Transaction::parse(&encoded).unwrap().0, *coinbase);
+            #[cfg(feature = "std")]
+            assert_eq!(Transaction::decode(&mut &encoded[..]).unwrap(), *coinbase);
+        }
+        assert!(truncated_push.inputs[0].script_sig.opcodes().is_err());
+    }
+
+    #[test]
+    fn test_total_output_value() {
+        let (mut tx, _) = Transaction::parse(&from_hex(BIP143_P2WPKH_TX).unwrap()).unwrap();
+        assert_eq!(tx.total_output_value(), Ok(112340000 + 223450000));
+        assert_eq!(tx.outputs[0].amount().to_string(), "1.12340000 BTC");
+
+        tx.outputs[1].value = u64::MAX;
+        assert_eq!(tx.total_output_value(), Err(Error::ValueOverflow));
+    }
+
+    #[test]
+    fn test_fee() {
+        let tx = Transaction::from_hex(BIP143_P2WPKH_TX).unwrap();
+        let output_value = tx.total_output_value().unwrap();
+        let values = [(tx.inputs[0].previous_output.clone(), 625_000_000), (tx.inputs[1].previous_output.clone(), 600_000_000)];
+        let lookup = |outpoint: &OutPoint| values.iter().find(|(prevout, _)| prevout == outpoint).map(|(_, value)| *value);
+        assert_eq!(tx.fee(lookup), Ok(1_225_000_000 - output_value));
+
+        assert_eq!(tx.fee(|_| None), Err(Error::UnknownPrevout(tx.inputs[0].previous_output.clone())));
+        assert_eq!(tx.fee(|_| Some(1)), Err(Error::OutputsExceedInputs(tx.txid())));
+        assert_eq!(tx.fee(|_| Some(u64::MAX)), Err(Error::ValueOverflow));
+    }
+
+    #[test]
+    fn test_dust() {
+        let txout = |value, script_pubkey: &str| TxOut { value, script_pubkey: Script::from_bytes(&from_hex(script_pubkey).unwrap()).unwrap() };
+        let p2pkh = txout(546, "76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac");
+        assert_eq!(p2pkh.dust_threshold(TxOut::DUST_RELAY_FEE), 546);
+        assert!(!p2pkh.is_dust(TxOut::DUST_RELAY_FEE));
+        assert!(p2pkh.is_dust(4));
+
+        let p2wpkh = txout(293, "0014751e76e8199196d454941c45d1b3a323f1433bd6");
+        assert_eq!(p2wpkh.dust_threshold(TxOut::DUST_RELAY_FEE), 294);
+        assert!(p2wpkh.is_dust(TxOut::DUST_RELAY_FEE));
+        let p2tr = txout(0, &format!("5120{}", "ab".repeat(32)));
+        assert_eq!(p2tr.dust_threshold(TxOut::DUST_RELAY_FEE), 330);
+
+        let op_return = txout(0, "6a0401020304");
+        assert_eq!(op_return.dust_threshold(TxOut::DUST_RELAY_FEE), 0);
+        assert!(!op_return.is_dust(TxOut::DUST_RELAY_FEE));
+    }
+
+    #[test]
+    fn test_is_standard() {
+        let tx = Transaction::from_hex(BIP143_P2WPKH_TX).unwrap();
+        assert_eq!(tx.is_standard(), Ok(()));
+        let script = |hex: &str| Script::from_bytes(&from_hex(hex).unwrap()).unwrap();
+
+        let mut modified = tx.clone();
+        modified.version = 3;
+        assert_eq!(modified.is_standard(), Err(NonStandardReason::Version(3)));
+
+        let mut modified = tx.clone();
+        modified.inputs[1].script_sig = Script::from_opcodes(&[OpCode::Dup]);
+        assert_eq!(modified.is_standard(), Err(NonStandardReason::ScriptSigNotPushOnly(1)));
+        modified.inputs[0].script_sig = Script::from_opcodes(&vec![OpCode::Push(vec![0; 520]); 4]);
+        assert_eq!(modified.is_standard(), Err(NonStandardReason::ScriptSigSize(0)));
+
+        let mut modified = tx.clone();
+        modified.outputs[1].script_pubkey = Script::from_opcodes(&[OpCode::Nop]);
+        assert_eq!(modified.is_standard(), Err(NonStandardReason::ScriptPubKey(1)));
+        let pubkey = "21025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee6357";
+        modified.outputs[1].script_pubkey = script(&format!("51{}54ae", pubkey.repeat(4)));
+        assert_eq!(modified.is_standard(), Err(NonStandardReason::BareMultisig(1)));
+        modified.outputs[1].script_pubkey = script(&format!("51{}53ae", pubkey.repeat(3)));
+        assert_eq!(modified.is_standard(), Ok(()));
+        // Outputs to future witness versions are standard, only spending them isn't
+        modified.outputs[1].script_pubkey = script("5210751e76e8199196d454941c45d1b3a323");
+        assert_eq!(modified.outputs[1].script_pubkey.classify(), ScriptType::WitnessUnknown);
+        assert_eq!(modified.is_standard(), Ok(()));
+        modified.outputs[1].script_pubkey = script("0010751e76e8199196d454941c45d1b3a323");
+        assert_eq!(modified.is_standard(), Err(NonStandardReason::ScriptPubKey(1)));
+
+        let mut modified = tx.clone();
+        modified.outputs[0] = TxOut { value: 0, script_pubkey: script(&format!("6a4c50{}", "aa".repeat(80))) };
+        assert_eq!(modified.is_standard(), Ok(()));
+        modified.outputs[0].script_pubkey = script(&format!("6a4c51{}", "aa".repeat(81)));
+        assert_eq!(modified.is_standard(), Err(NonStandardReason::DataCarrierSize(0)));
+        modified.outputs[0].script_pubkey = script("6a76");
+        assert_eq!(modified.is_standard(), Err(NonStandardReason::ScriptPubKey(0)));
+        modified.outputs[0].script_pubkey = script("6a0401020304");
+        modified.outputs.push(modified.outputs[0].clone());
+        assert_eq!(modified.is_standard(), Err(NonStandardReason::MultiOpReturn));
+
+        let mut modified = tx.clone();
+        modified.outputs[0].value = 545;
+        assert_eq!(modified.is_standard(), Err(NonStandardReason::Dust(0)));
+        modified.outputs = vec![modified.outputs[1].clone(); 12_000];
+        assert!(matches!(modified.is_standard(), Err(NonStandardReason::Weight(_))));
+    }
+
+    #[test]
+    fn test_lock_time() {
+        let mut tx = Transaction::from_hex(BIP143_P2WPKH_TX).unwrap();
+        assert_eq!(tx.lock_time(), LockTime::Height(0x11));
+        tx.locktime = Transaction::LOCKTIME_THRESHOLD;
+        assert_eq!(tx.lock_time(), LockTime::Time(500_000_000));
+        tx.locktime = Transaction::LOCKTIME_THRESHOLD - 1;
+        assert_eq!(tx.lock_time(), LockTime::Height(499_999_999));
+
+        // The first input has a non-final 0xffffffee sequence
+        tx.inputs[0].sequence = 0xFFFFFFFF;
+        assert_eq!(tx.lock_time(), LockTime::Disabled);
+
+        let tx = Transaction::from_hex(BLOCK_170_TX).unwrap();
+        assert_eq!(tx.lock_time(), LockTime::Disabled);
+    }
+
+    #[test]
+    fn test_is_rbf_signaling() {
+        // The first input has a 0xffffffee sequence
+        let mut tx = Transaction::from_hex(BIP143_P2WPKH_TX).unwrap();
+        assert!(tx.is_rbf_signaling());
+
+        tx.inputs[0].sequence = 0xFFFFFFFE;
+        assert!(!tx.is_rbf_signaling());
+        tx.inputs[1].sequence = 0xFFFFFFFD;
+        assert!(tx.is_rbf_signaling());
+        tx.inputs[1].sequence = 0xFFFFFFFF;
+        assert!(!tx.is_rbf_signaling());
+        tx.inputs[1].sequence = 0;
+        assert!(tx.is_rbf_signaling());
+
+        assert!(!Transaction::from_hex(BLOCK_170_TX).unwrap().is_rbf_signaling());
+    }
+
+    #[test]
+    fn test_relative_locktime() {
+        let mut txin = Transaction::from_hex(BIP143_P2WPKH_TX).unwrap().inputs.remove(0);
+        // 0xffffffee has the disable flag set
+        assert_eq!(txin.relative_locktime(), None);
+
+        txin.sequence = 144;
+        assert_eq!(txin.relative_locktime(), Some(RelativeLockTime::Blocks(144)));
+        txin.sequence = TxIn::SEQUENCE_LOCKTIME_TYPE_FLAG | 0x1234;
+        assert_eq!(txin.relative_locktime(), Some(RelativeLockTime::Time(0x1234)));
+        // Bits outside of the flags and the low 16 bits are ignored
+        txin.sequence = 0x003f_0000 | 10;
+        assert_eq!(txin.relative_locktime(), Some(RelativeLockTime::Blocks(10)));
+        txin.sequence = TxIn::SEQUENCE_LOCKTIME_DISABLE_FLAG | 10;
+        assert_eq!(txin.relative_locktime(), None);
+    }
+
+    #[test]
+    fn test_has_duplicate_inputs() {
+        let mut tx = Transaction::from_hex(BIP143_P2WPKH_TX).unwrap();
+        assert!(!tx.has_duplicate_inputs());
+
+        // Same txid, different vout, is fine
+        tx.inputs[1].previous_output.txid = tx.inputs[0].previous_output.txid;
+        assert!(!tx.has_duplicate_inputs());
+        tx.inputs[1].previous_output.vout = tx.inputs[0].previous_output.vout;
+        assert!(tx.has_duplicate_inputs());
+    }
+
+    #[test]
+    fn test_get_output() {
+        let tx = Transaction::from_hex(BLOCK_170_TX).unwrap();
+        assert_eq!(tx.get_output(1), Some(&tx.outputs[1]));
+        assert_eq!(tx.output_at(0), Some(&tx.outputs[0]));
+        assert_eq!(tx.get_output(2), None);
+        assert_eq!(tx.get_output(u32::MAX), None);
+        assert_eq!(tx.output_at(usize::MAX), None);
+    }
+
+    #[test]
+    fn test_summary() {
+        let tx = Transaction::from_hex(BIP143_P2WPKH_TX).unwrap();
+        assert_eq!(
+            tx.summary(),
+            TxSummary {
+                txid: tx.txid(),
+                total_size: 343,
+                vsize: 261,
+                weight: 1042,
+                input_count: 2,
+                output_count: 2,
+                total_output_value: Some(112_340_000 + 223_450_000),
+                is_coinbase: false,
+                is_segwit: true,
+            }
+        );
+
+        let mut tx = Transaction::from_hex(BLOCK_170_TX).unwrap();
+        let summary = tx.summary();
+        assert_eq!((summary.input_count, summary.output_count, summary.is_segwit), (1, 2, false));
+        assert_eq!(summary.total_size, tx.base_size());
+        tx.outputs[0].value = u64::MAX;
+        assert_eq!(tx.summary().total_output_value, None);
+    }
+
+    #[test]
+    fn test_weight() {
+        let (tx, _) = Transaction::parse(&from_hex(BIP143_P2WPKH_TX).unwrap()).unwrap();
+        assert_eq!(tx.weight(), 233 * 3 + 343);
+        assert_eq!(tx.vsize(), 261);
+
+        let block = crate::Block::from_hex_str(include_str!("../block.hex")).unwrap();
+        for tx in &block.transactions {
+            let mut bytes = Vec::new();
+            tx.encode(&mut bytes);
+            assert_eq!(tx.weight(), bytes.len() * 4);
+            assert_eq!(tx.vsize(), bytes.len());
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_outpoint_map_key() {
+        use std::collections::{HashMap, HashSet};
+
+        let block = crate::Block::from_hex_str(include_str!("../block.hex")).unwrap();
+        let spent = block.transactions[1..].iter().flat_map(|tx| tx.inputs.iter().map(|txin| txin.previous_output.clone())).collect::<HashSet<_>>();
+        assert_eq!(spent.len(), block.transactions[1..].iter().map(|tx| tx.inputs.len()).sum::<usize>());
+
+        let by_txid = block.transactions.iter().map(|tx| (tx.txid(), tx)).collect::<HashMap<_, _>>();
+        assert_eq!(by_txid[&block.transactions[3].txid()], &block.transactions[3]);
+        assert_eq!(block.transactions.iter().collect::<HashSet<_>>().len(), block.transactions.len());
+    }
+
+    #[test]
+    fn test_redeem_script() {
+        let block = crate::Block::from_hex_str(include_str!("../block.hex")).unwrap();
+        let txin = &block.transactions[27].inputs[0];
+        let pushed = *txin.script_sig.push_bytes().last().unwrap();
+        let p2sh = Script::from_opcodes(&[OpCode::Hash160, OpCode::Push(hash160(pushed).to_vec()), OpCode::Equal]);
+        let redeem_script = txin.redeem_script(&p2sh).unwrap();
+        assert_eq!(redeem_script.classify(), crate::ScriptType::Multisig);
+        assert_eq!(redeem_script.as_multisig().map(|(m, keys)| (m, keys.len())), Some((2, 3)));
+
+        // The pushed data must be what the script hash commits to
+        let other_p2sh = Script::from_opcodes(&[OpCode::Hash160, OpCode::Push(vec![0; 20]), OpCode::Equal]);
+        assert_eq!(txin.redeem_script(&other_p2sh), None);
+        let mut txin = txin.clone();
+        txin.script_sig = Script(vec![]);
+        assert_eq!(txin.redeem_script(&p2sh), None);
+        txin.script_sig = Script::from_opcodes(&[OpCode::Push(pushed.to_vec()), OpCode::Dup]);
+        assert_eq!(txin.redeem_script(&p2sh), None);
+
+        // Committed to by the script hash, but the push runs past the end of the script
+        let truncated_push = vec![0x51, 0x4c, 0x10, 0xaa];
+        let truncated_p2sh = Script::from_opcodes(&[OpCode::Hash160, OpCode::Push(hash160(&truncated_push).to_vec()), OpCode::Equal]);
+        txin.script_sig = Script::from_opcodes(&[OpCode::False, OpCode::Push(truncated_push)]);
+        assert_eq!(txin.redeem_script(&truncated_p2sh), None);
+
+        // A P2PKH scriptSig ends with a public key, that's not a script
+        let (sig, pubkey) = (vec![0x30; 71], vec![0x02; 33]);
+        txin.script_sig = Script::from_opcodes(&[OpCode::Push(sig), OpCode::Push(pubkey.clone())]);
+        let p2pkh = Script::from_opcodes(&[OpCode::Dup, OpCode::Hash160, OpCode::Push(hash160(&pubkey).to_vec()), OpCode::EqualVerify, OpCode::CheckSig]);
+        assert_eq!(txin.redeem_script(&p2pkh), None);
+        // Nor is the signature of a P2PK spend
+        let tx = Transaction::from_hex(BLOCK_170_TX).unwrap();
+        let p2pk = Script(from_hex("410411db93e1dcdb8a016b49840f8c53bc1eb68a382e97b1482ecad7b148a6909a5cb2e0eaddfb84ccf9744464f82e160bfa9b8b64f9d4c03f999b8643f656b412a3ac").unwrap());
+        assert_eq!(tx.inputs[0].redeem_script(&p2pk), None);
+    }
+
+    #[test]
+    fn test_witness_script() {
+        let (tx, _) = Transaction::parse(&from_hex(BIP143_P2WPKH_TX).unwrap()).unwrap();
+        let p2pk = Script(from_hex("2103c9f4836b9a4f77fc0d81f7bcb01b7f1b35916864b9476c241ce9fc198bd25432ac").unwrap());
+        assert!(tx.inputs[0].witness().is_empty());
+        assert_eq!(tx.inputs[0].witness_script(&p2pk), None);
+        let p2wpkh = Script(from_hex("00141d0f172a0ecb48aee1be1f2687d2963ae33f71a1").unwrap());
+        assert_eq!(tx.inputs[1].witness().len(), 2);
+        assert_eq!(tx.inputs[1].witness_script(&p2wpkh), None);
+
+        let witness_script = Script::from_asm("OP_2 025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee6357 03f6b60d638b3e578fe82ed28dd77f3896759f7fe376c8065d2fb74394eb0d85a1 OP_2 OP_CHECKMULTISIG").unwrap();
+        let p2wsh = Script::from_opcodes(&[OpCode::False, OpCode::Push(sha256(witness_script.as_bytes()).to_vec())]);
+        let mut txin = tx.inputs[1].clone();
+        txin.witness = vec![vec![], tx.inputs[1].witness[0].clone(), witness_script.to_bytes()];
+        assert_eq!(txin.witness_script(&p2wsh), Some(witness_script.clone()));
+        assert_eq!(txin.witness_script(&p2wpkh), None);
+        let other_p2wsh = Script::from_opcodes(&[OpCode::False, OpCode::Push(vec![0; 32])]);
+        assert_eq!(txin.witness_script(&other_p2wsh), None);
+
+        // Nested in P2SH, the scriptSig pushes the P2WSH program
+        let p2sh = Script::from_opcodes(&[OpCode::Hash160, OpCode::Push(hash160(p2wsh.as_bytes()).to_vec()), OpCode::Equal]);
+        assert_eq!(txin.witness_script(&p2sh), None);
+        txin.script_sig = Script::from_opcodes(&[OpCode::Push(p2wsh.to_bytes())]);
+        assert_eq!(txin.witness_script(&p2sh), Some(witness_script.clone()));
+        assert_eq!(txin.witness_script(&p2wsh), None);
+
+        // A taproot key path spend only has the signature, a script path spend ends with the
+        // control block
+        let p2tr = Script::from_opcodes(&[OpCode::num(1).unwrap(), OpCode::Push(vec![0x79; 32])]);
+        let mut txin = tx.inputs[1].clone();
+        txin.witness = vec![vec![0x01; 64]];
+        assert_eq!(txin.witness_script(&p2tr), None);
+        txin.witness = vec![vec![0x01; 64], witness_script.to_bytes(), vec![0xc0; 33]];
+        assert_eq!(txin.witness_script(&p2tr), None);
+    }
+
+    #[test]
+    fn test_outpoint_str() {
+        let s = "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16:1";
+        let outpoint = s.parse::<OutPoint>().unwrap();
+        assert_eq!(outpoint.vout, 1);
+        assert_eq!(outpoint.txid[0], 0x16);
+        assert_eq!(outpoint.to_string(), s);
+
+        assert_eq!("f4184fc5".parse::<OutPoint>(), Err(Error::MissingOutPointSeparator));
+        assert_eq!("f4184fc5:0".parse::<OutPoint>(), Err(Error::InvalidHashLength(4)));
+        assert_eq!("zz:0".parse::<OutPoint>(), Err(Error::InvalidHexDigit('z')));
+        assert_eq!(format!("{}x", &s[..s.len() - 1]).parse::<OutPoint>(), Err(Error::InvalidVout("x".into())));
+        assert_eq!(format!("{}4294967296", &s[..s.len() - 1]).parse::<OutPoint>(), Err(Error::InvalidVout("4294967296".into())));
+    }
+
+    #[test]
+    fn test_sizes() {
+        let tx_bytes = from_hex(BIP143_P2WPKH_TX).unwrap();
+        let (tx, _) = Transaction::parse(&tx_bytes).unwrap();
+        assert_eq!(tx.total_size(), tx_bytes.len());
+        assert_eq!(tx.base_size(), 233);
+
+        let block_bytes = from_hex(include_str!("../block.hex").trim()).unwrap();
+        let (_, mut bytes) = crate::BlockHeader::parse(&block_bytes).unwrap();
+        let (count, remainder) = VarInt::parse(bytes).unwrap();
+        bytes = remainder;
+        for _ in 0..count.0 {
+            let (tx, remainder) = Transaction::parse(bytes).unwrap();
+            assert_eq!(tx.total_size(), bytes.len() - remainder.len());
+            assert_eq!(tx.base_size(), tx.total_size());
+            bytes = remainder;
+        }
+    }
+}
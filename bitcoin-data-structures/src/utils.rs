@@ -1,42 +1,257 @@
-pub type Error = Box<dyn std::error::Error>;
+use crate::prelude::*;
 
-pub fn from_hex(s: &str) -> Result<Vec<u8>, Error> {
-    fn char_to_u8(c: char) -> Result<u8, Error> {
-        Ok(c.to_digit(16).ok_or("Invalid hex digit")? as u8)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    OddHexLength,
+    InvalidHexDigit(char),
+    InsufficientBytes { needed: usize, got: usize },
+    TrailingBytes(usize),
+    NonCanonicalVarInt(u64),
+    UnknownOpCodeName(String),
+    StackUnderflow,
+    UnsupportedOpCode(u8),
+    InvalidBase58Char(char),
+    InvalidChecksum,
+    InvalidBech32Char(char),
+    InvalidBech32Length(usize),
+    InvalidBech32Padding,
+    MissingBech32Separator,
+    MixedCaseBech32,
+    InvalidWitnessVersion(u8),
+    InvalidWitnessProgramLength(usize),
+    InvalidHashLength(usize),
+    #[cfg(feature = "std")]
+    Io(std::io::ErrorKind),
+    ValueOverflow,
+    AmountOutOfRange,
+    InvalidMagic([u8; 4]),
+    UnexpectedCommand(String),
+    MissingOutPointSeparator,
+    InvalidVout(String),
+    PushTooLarge(usize),
+    ScriptNumTooLarge { len: usize, max: usize },
+    NonMinimalScriptNum,
+    UnknownPrevout(crate::OutPoint),
+    OutputsExceedInputs(crate::Txid),
+    DuplicateInputs(crate::Txid),
+    EmptyBlock,
+    MissingCoinbase,
+    CoinbaseInputCount(usize),
+    CoinbaseScriptSize(usize),
+    ExtraCoinbase(usize),
+    LimitExceeded { got: u64, max: usize },
+    SuperfluousWitness,
+    InvalidSmallNum(u8),
+    AssignedOpCode(u8),
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Error::OddHexLength => write!(f, "Odd number of chars"),
+            Error::InvalidHexDigit(c) => write!(f, "Invalid hex digit {:?}", c),
+            Error::InsufficientBytes { needed, got } => write!(f, "Insufficient bytes: needed {}, got {}", needed, got),
+            Error::TrailingBytes(n) => write!(f, "{} trailing bytes after parsing", n),
+            Error::NonCanonicalVarInt(val) => write!(f, "Non-canonical VarInt encoding for {}", val),
+            Error::UnknownOpCodeName(name) => write!(f, "Unknown opcode {}", name),
+            Error::StackUnderflow => write!(f, "Not enough items on the stack"),
+            Error::UnsupportedOpCode(op) => write!(f, "Opcode 0x{:02x} is not supported by the interpreter", op),
+            Error::InvalidBase58Char(c) => write!(f, "Invalid base58 character {:?}", c),
+            Error::InvalidChecksum => write!(f, "Invalid checksum"),
+            Error::InvalidBech32Char(c) => write!(f, "Invalid bech32 character {:?}", c),
+            Error::InvalidBech32Length(len) => write!(f, "Invalid bech32 string length {}", len),
+            Error::InvalidBech32Padding => write!(f, "Invalid padding in bech32 data"),
+            Error::MissingBech32Separator => write!(f, "Missing bech32 separator"),
+            Error::MixedCaseBech32 => write!(f, "Mixed case bech32 string"),
+            Error::InvalidWitnessVersion(v) => write!(f, "Invalid witness version {}", v),
+            Error::InvalidWitnessProgramLength(len) => write!(f, "Invalid witness program length {}", len),
+            Error::InvalidHashLength(len) => write!(f, "Invalid hash length {}, expected 32 bytes", len),
+            #[cfg(feature = "std")]
+            Error::Io(kind) => write!(f, "I/O error: {}", kind),
+            Error::ValueOverflow => write!(f, "Value overflow"),
+            Error::AmountOutOfRange => write!(f, "Amount out of range"),
+            Error::InvalidMagic(magic) => write!(f, "Unexpected network magic {}", to_hex(magic)),
+            Error::UnexpectedCommand(command) => write!(f, "Unexpected P2P command {:?}", command),
+            Error::MissingOutPointSeparator => write!(f, "Missing ':' between txid and vout"),
+            Error::InvalidVout(vout) => write!(f, "Invalid output index {:?}", vout),
+            Error::PushTooLarge(len) => write!(f, "Push of {} bytes exceeds the 520 bytes limit", len),
+            Error::ScriptNumTooLarge { len, max } => write!(f, "Script number of {} bytes exceeds the {} bytes limit", len, max),
+            Error::NonMinimalScriptNum => write!(f, "Non-minimal script number encoding"),
+            Error::UnknownPrevout(outpoint) => write!(f, "Unknown value for the output spent by {}", outpoint),
+            Error::OutputsExceedInputs(txid) => write!(f, "Outputs of {} exceed its inputs", txid),
+            Error::DuplicateInputs(txid) => write!(f, "Transaction {} spends the same output twice", txid),
+            Error::EmptyBlock => write!(f, "Block has no transactions"),
+            Error::MissingCoinbase => write!(f, "First transaction is not a coinbase"),
+            Error::CoinbaseInputCount(count) => write!(f, "Coinbase has {} inputs, expected 1", count),
+            Error::CoinbaseScriptSize(len) => write!(f, "Coinbase scriptSig of {} bytes, expected 2 to 100", len),
+            Error::ExtraCoinbase(index) => write!(f, "Transaction {} is a coinbase but not the first one", index),
+            Error::LimitExceeded { got, max } => write!(f, "Length {} exceeds the parse limit of {}", got, max),
+            Error::SuperfluousWitness => write!(f, "Witness flag set but every witness is empty"),
+            Error::InvalidSmallNum(n) => write!(f, "No OP_n opcode pushes {}, expected 1 to 16", n),
+            Error::AssignedOpCode(byte) => write!(f, "Byte 0x{:02x} is an assigned opcode", byte),
+        }
+    }
+}
+
+impl Error {
+    /// Offset in an input of `input_len` bytes where parsing stopped, for the errors that carry
+    /// the number of bytes left. Parsers only ever read suffixes of their input, and scripts are
+    /// tokenized lazily, so the count is always from the end of the whole input
+    pub fn failure_offset(&self, input_len: usize) -> Option<usize> {
+        match self {
+            Error::InsufficientBytes { got, .. } | Error::TrailingBytes(got) => input_len.checked_sub(*got),
+            _ => None,
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err.kind())
+    }
+}
+
+pub(crate) fn check_len(bytes: &[u8], needed: usize) -> Result<(), Error> {
+    if bytes.len() < needed {
+        return Err(Error::InsufficientBytes { needed, got: bytes.len() });
+    }
+
+    Ok(())
+}
+
+pub(crate) fn take_bytes<const N: usize>(bytes: &[u8]) -> Result<([u8; N], &[u8]), Error> {
+    check_len(bytes, N)?;
+    let (val, remainder) = bytes.split_at(N);
+    Ok((val.try_into().expect("length checked above"), remainder))
+}
+
+/// Nibble value of every ASCII hex digit, `0xFF` for any other byte
+const HEX_DIGITS: [u8; 256] = {
+    let mut table = [0xFF; 256];
+    let mut i = 0;
+    while i < 10 {
+        table[b'0' as usize + i] = i as u8;
+        i += 1;
     }
+    let mut i = 0;
+    while i < 6 {
+        table[b'a' as usize + i] = 10 + i as u8;
+        table[b'A' as usize + i] = 10 + i as u8;
+        i += 1;
+    }
+    table
+};
 
-    let chars = s.chars().collect::<Vec<_>>();
-    let chunks_iter = chars
-        .chunks_exact(2);
-    if !chunks_iter.remainder().is_empty() {
-        return Err("Odd number of chars".into());
+pub fn from_hex(s: &str) -> Result<Vec<u8>, Error> {
+    let bytes = s.as_bytes();
+    if !bytes.len().is_multiple_of(2) {
+        return Err(Error::OddHexLength);
     }
-    
-    chunks_iter.map(|c| Ok(char_to_u8(c[0])? << 4 | char_to_u8(c[1])?)).collect::<Result<Vec<_>, _>>()
+
+    let nibble = |pos: usize| match HEX_DIGITS[bytes[pos] as usize] {
+        // Report the whole char, which can be multi-byte for non-ASCII input
+        0xFF => Err(Error::InvalidHexDigit(s.get(pos..).and_then(|s| s.chars().next()).unwrap_or(char::REPLACEMENT_CHARACTER))),
+        val => Ok(val),
+    };
+
+    (0..bytes.len()).step_by(2).map(|pos| Ok(nibble(pos)? << 4 | nibble(pos + 1)?)).collect()
+}
+
+/// Lenient `from_hex` for pasted input: a leading `0x`/`0X` is stripped and ASCII whitespace
+/// anywhere is skipped. Use `from_hex` to parse consensus data
+pub fn from_hex_relaxed(s: &str) -> Result<Vec<u8>, Error> {
+    let s = s.trim_start();
+    let s = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")).unwrap_or(s);
+    from_hex(&s.chars().filter(|c| !c.is_ascii_whitespace()).collect::<String>())
 }
 
 pub fn to_hex<T: AsRef<[u8]>>(bytes: &T) -> String {
-    fn u8_to_char(val: u8) -> char {
-        match val & 0x0F {
-            v @ 0..=9 => char::from(0x30 + v),
-            v @ 0xA..=0xF => char::from(87 + v),
-            _ => unreachable!()
-        }
+    to_hex_with_case(bytes, false)
+}
+
+pub fn to_hex_upper<T: AsRef<[u8]>>(bytes: &T) -> String {
+    to_hex_with_case(bytes, true)
+}
+
+pub fn to_hex_with_case<T: AsRef<[u8]>>(bytes: &T, upper: bool) -> String {
+    let digits = if upper { b"0123456789ABCDEF" } else { b"0123456789abcdef" };
+    let u8_to_char = |val: u8| char::from(digits[(val & 0x0F) as usize]);
+
+    bytes.as_ref().iter().flat_map(|b| [u8_to_char(*b >> 4), u8_to_char(*b)]).collect()
+}
+
+/// `xxd`-style dump: the offset, 16 bytes in groups of two and their printable ASCII chars.
+/// `start_offset` is added to the offsets, to dump a slice of a larger buffer
+pub fn hexdump(bytes: &[u8], start_offset: usize) -> String {
+    let mut out = String::new();
+    for (line, chunk) in bytes.chunks(16).enumerate() {
+        let hex = chunk.chunks(2).map(|pair| to_hex(&pair)).collect::<Vec<_>>().join(" ");
+        let ascii = chunk.iter().map(|b| if b.is_ascii_graphic() || *b == b' ' { *b as char } else { '.' }).collect::<String>();
+        out += &format!("{:08x}: {:<39}  {}\n", start_offset + line * 16, hex, ascii);
     }
+    out
+}
 
-    bytes.as_ref().iter().map(|b| [u8_to_char(*b >> 4), u8_to_char(*b)]).flatten().collect()
+/// `err` followed by a dump of the bytes around the point of failure, when it's known
+pub fn describe_parse_error(bytes: &[u8], err: &Error) -> String {
+    match err.failure_offset(bytes.len()) {
+        Some(offset) => {
+            let start = (offset / 16).saturating_sub(2) * 16;
+            let end = (start + 5 * 16).min(bytes.len());
+            format!("{} at offset {}\n{}", err, offset, hexdump(&bytes[start..end], start))
+        },
+        None => err.to_string(),
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn assert_bytes_eq(actual: &[u8], expected: &[u8]) {
+    let Some(offset) = actual.iter().zip(expected).position(|(a, b)| a != b).or_else(|| {
+        (actual.len() != expected.len()).then(|| actual.len().min(expected.len()))
+    }) else {
+        return;
+    };
+
+    let start = offset.saturating_sub(16);
+    panic!(
+        "first mismatch at offset {} (actual len {}, expected len {})\n  actual: {}\nexpected: {}",
+        offset,
+        actual.len(),
+        expected.len(),
+        to_hex(&&actual[start..(offset + 16).min(actual.len())]),
+        to_hex(&&expected[start..(offset + 16).min(expected.len())]),
+    );
 }
 
 #[cfg(test)]
 mod test {
-    use crate::utils::{from_hex, to_hex};
+    use crate::prelude::*;
+    use crate::utils::{describe_parse_error, from_hex, from_hex_relaxed, hexdump, to_hex, to_hex_upper, to_hex_with_case, Error};
+    use crate::{Block, Parse};
 
     #[test]
     fn test_from_hex() {
-        assert_eq!(from_hex("00").map_err(|e| e.to_string()), Ok(vec![0x00]));
-        assert_eq!(from_hex("aabb").map_err(|e| e.to_string()), Ok(vec![0xaa, 0xbb]));
+        assert_eq!(from_hex("00"), Ok(vec![0x00]));
+        assert_eq!(from_hex("aabb"), Ok(vec![0xaa, 0xbb]));
+        assert_eq!(from_hex("000"), Err(Error::OddHexLength));
+        assert_eq!(from_hex("0x"), Err(Error::InvalidHexDigit('x')));
+        assert_eq!(from_hex("AaBbCcDdEeFf09"), Ok(vec![0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff, 0x09]));
+        assert_eq!(from_hex("g0"), Err(Error::InvalidHexDigit('g')));
+        assert_eq!(from_hex("é0"), Err(Error::OddHexLength));
+        assert_eq!(from_hex("0é0"), Err(Error::InvalidHexDigit('é')));
         assert_eq!(from_hex("000").map_err(|e| e.to_string()), Err("Odd number of chars".into()));
-        assert_eq!(from_hex("0x").map_err(|e| e.to_string()), Err("Invalid hex digit".into()));
+    }
+
+    #[test]
+    fn test_from_hex_relaxed() {
+        assert_eq!(from_hex_relaxed("0xaabb"), Ok(vec![0xaa, 0xbb]));
+        assert_eq!(from_hex_relaxed("  0XAA bb\n cc\r\n"), Ok(vec![0xaa, 0xbb, 0xcc]));
+        assert_eq!(from_hex_relaxed("aa b"), Err(Error::OddHexLength));
+        assert_eq!(from_hex_relaxed("aa0x"), Err(Error::InvalidHexDigit('x')));
+        assert_eq!(from_hex(" aa "), Err(Error::InvalidHexDigit(' ')));
     }
 
     #[test]
@@ -44,5 +259,54 @@ mod test {
         assert_eq!(to_hex(&vec![0xAA, 0xBB]), String::from("aabb"));
         assert_eq!(to_hex(&vec![0x00]), String::from("00"));
         assert_eq!(to_hex(&vec![0x99, 0xFF]), String::from("99ff"));
+        assert_eq!(to_hex_upper(&vec![0x99, 0xFF, 0x0a]), String::from("99FF0A"));
+        assert_eq!(to_hex_with_case(&[0xab], false), String::from("ab"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_hexdump() {
+        assert_eq!(
+            hexdump(b"\x01\x00\x00\x00Hello, world!\xff\x7f\x00", 0x20),
+            "00000020: 0100 0000 4865 6c6c 6f2c 2077 6f72 6c64  ....Hello, world\n\
+             00000030: 21ff 7f00                                !...\n"
+        );
+        assert_eq!(hexdump(&[], 0), "");
+    }
+
+    #[test]
+    fn test_describe_parse_error() {
+        let bytes = from_hex(include_str!("../block.hex").trim()).unwrap();
+        let truncated = &bytes[..1000];
+        let err = Block::parse_exact(truncated).unwrap_err();
+        let offset = err.failure_offset(truncated.len()).unwrap();
+        assert!(offset < truncated.len());
+
+        let description = describe_parse_error(truncated, &err);
+        let mut lines = description.lines();
+        assert_eq!(lines.next(), Some(format!("{} at offset {}", err, offset).as_str()));
+        assert!(lines.any(|line| line.starts_with(&format!("{:08x}:", offset / 16 * 16))));
+
+        // The block ends inside the height push of the coinbase scriptSig, which starts after the
+        // header, the transaction count, version, input count, outpoint and length
+        let script_sig_offset = 80 + 1 + 4 + 1 + 36 + 1;
+        assert_eq!(&bytes[script_sig_offset..script_sig_offset + 4], &[0x03, 0xb1, 0x47, 0x07]);
+        let truncated = &bytes[..script_sig_offset + 2];
+        let err = Block::parse_exact(truncated).unwrap_err();
+        assert_eq!(err, Error::InsufficientBytes { needed: 100, got: 2 });
+        assert_eq!(err.failure_offset(truncated.len()), Some(script_sig_offset));
+        assert!(describe_parse_error(truncated, &err).starts_with(&format!("{} at offset {}\n", err, script_sig_offset)));
+
+        // With the whole scriptSig there, a truncated push only fails once it's tokenized
+        let mut bytes_with_truncated_push = bytes.clone();
+        bytes_with_truncated_push[script_sig_offset] = 0x4c;
+        let block = Block::parse_exact(&bytes_with_truncated_push).unwrap();
+        let coinbase_script = block.transactions[0].inputs[0].script_sig.clone();
+        let err = coinbase_script.opcodes().unwrap_err();
+        assert_eq!(err.failure_offset(coinbase_script.size()), Some(0));
+
+        let mut trailing = bytes.clone();
+        trailing.push(0xff);
+        assert_eq!(Block::parse_exact(&trailing).unwrap_err().failure_offset(trailing.len()), Some(bytes.len()));
+        assert_eq!(describe_parse_error(&bytes, &Error::OddHexLength), "Odd number of chars");
+    }
+}
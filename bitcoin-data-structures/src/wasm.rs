@@ -0,0 +1,24 @@
+use wasm_bindgen::prelude::*;
+
+use crate::block::Block;
+use crate::prelude::*;
+
+/// Parses a hex-encoded block into the same JSON produced by the `serde` feature. Errors are
+/// thrown as JavaScript `Error`s carrying the `Display` message
+#[wasm_bindgen]
+pub fn parse_block_json(hex: &str) -> Result<String, JsValue> {
+    let block = Block::from_hex_str(hex).map_err(|e| JsError::new(&e.to_string()))?;
+    Ok(serde_json::to_string(&block).map_err(|e| JsError::new(&e.to_string()))?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_block_json() {
+        let block = Block::from_hex_str(include_str!("../block.hex")).unwrap();
+        let json = parse_block_json(include_str!("../block.hex")).unwrap();
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&json).unwrap(), serde_json::to_value(&block).unwrap());
+    }
+}